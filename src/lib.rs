@@ -0,0 +1,69 @@
+//! A small feed forward neural network library built on ndarray.
+
+mod net;
+
+pub use net::block::{Block, BlockGradients};
+pub use net::builder::NetworkBuilder;
+pub use net::conv::{Conv1D, Conv2D, Flatten, Padding, Shape2, Shape3};
+pub use net::dataset::{Column, CsvOptions, Dataset};
+pub use net::dropout::Dropout;
+pub use net::error::Error;
+pub use net::gradient_check::gradient_check;
+pub use net::gradients::Gradients;
+pub use net::layer::{ActivationLayer, DenseCore, DenseGradients, HiddenLayer, InputLayer, Layer, OutputLayer};
+pub use net::network::Network;
+pub use net::normalization::{BatchNorm, LayerNorm, NormGradients, RmsNorm};
+pub use net::pooling::{AvgPool2D, GlobalAveragePool1D, GlobalMaxPool1D, MaxPool2D, Pool2D};
+pub use net::serialization::FORMAT_VERSION;
+pub use net::trainer::{evaluate, EarlyStopping, History, TrainConfig, Trainer};
+
+pub mod activations {
+    pub use crate::net::activation_functions::*;
+}
+
+pub mod callbacks {
+    pub use crate::net::callbacks::*;
+}
+
+pub mod idx {
+    pub use crate::net::idx::*;
+}
+
+pub mod initializers {
+    pub use crate::net::weight_functions::*;
+}
+
+pub mod losses {
+    pub use crate::net::loss_functions::*;
+}
+
+pub mod neuron {
+    pub use crate::net::neuron::*;
+}
+
+pub mod optimizers {
+    pub use crate::net::optimizers::*;
+}
+
+pub mod regularizers {
+    pub use crate::net::regularizers::*;
+}
+
+pub mod schedules {
+    pub use crate::net::schedules::*;
+}
+
+pub mod prelude {
+    pub use crate::activations::Activation;
+    pub use crate::callbacks::{Callback, Control};
+    pub use crate::initializers::Initializer;
+    pub use crate::losses::Loss;
+    pub use crate::optimizers::Optimizer;
+    pub use crate::regularizers::Regularizer;
+    pub use crate::schedules::LrSchedule;
+    pub use crate::{
+        ActivationLayer, AvgPool2D, BatchNorm, Block, Conv1D, Conv2D, CsvOptions, Dataset, Dropout, EarlyStopping, Error,
+        Flatten, GlobalAveragePool1D, GlobalMaxPool1D, Gradients, HiddenLayer, History, InputLayer, Layer, LayerNorm, MaxPool2D,
+        Network, NetworkBuilder, OutputLayer, Padding, RmsNorm, TrainConfig, Trainer,
+    };
+}
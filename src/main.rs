@@ -1,15 +1,13 @@
+use firstnet::prelude::*;
 use ndarray::array;
 
-use crate::net::{
-    activation_functions::{self, sigmoid},
-    layer::{HiddenLayer, InputLayer, OutputLayer},
-    network::Network,
-    weight_functions::xavier_init,
-};
-
-mod net;
-
-fn main() {
-    println!("Hello, world!");
+fn main() -> Result<(), Error> {
+    let mut network = NetworkBuilder::new(2)
+        .dense(3, Activation::Sigmoid)
+        .dense(1, Activation::Identity)
+        .build()?;
 
+    let outputs = network.forward_batch(&array![[0.5, -0.5], [-0.5, 0.5]])?;
+    println!("{}", outputs);
+    Ok(())
 }
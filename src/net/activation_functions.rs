@@ -3,25 +3,35 @@
  * Binary Classification—Sigmoid/Logistic Activation Function
  * Multiclass Classification—Softmax
  * Multilabel Classification—Sigmoid
+ *
+ * All derivatives take the pre-activation value x, not the activated output.
  */
 
-use std::f32::consts::E;
+use ndarray::{Array2, Axis};
+use std::f32::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+const SELU_ALPHA: f32 = 1.673_263_2;
+const SELU_SCALE: f32 = 1.050_701;
 
 // should not be used in hidden layers
 pub fn sigmoid(x: f32) -> f32 {
-    1.0 / (1.0 + E.powf(-x))
+    1.0 / (1.0 + (-x).exp())
 }
 
 pub fn sigmoid_derivative(x: f32) -> f32 {
-    x * (1.0 - x)
+    let s = sigmoid(x);
+    s * (1.0 - s)
 }
 
 pub fn tanh(x: f32) -> f32 {
-    (1.0 - (-x).exp()) / (1.0 + (-x).exp())
+    x.tanh()
 }
 
 pub fn tanh_derivative(x: f32) -> f32 {
-    1.0 - x * x
+    let t = x.tanh();
+    1.0 - t * t
 }
 
 // should only be used in hidden layer
@@ -41,15 +51,344 @@ pub fn relu_derivative(x: f32) -> f32 {
     }
 }
 
+pub fn leaky_relu(x: f32, alpha: f32) -> f32 {
+    if x < 0.0 {
+        alpha * x
+    } else {
+        x
+    }
+}
+
+pub fn leaky_relu_derivative(x: f32, alpha: f32) -> f32 {
+    if x < 0.0 {
+        alpha
+    } else {
+        1.0
+    }
+}
+
+pub fn elu(x: f32, alpha: f32) -> f32 {
+    if x < 0.0 {
+        alpha * x.exp_m1()
+    } else {
+        x
+    }
+}
+
+pub fn elu_derivative(x: f32, alpha: f32) -> f32 {
+    if x < 0.0 {
+        alpha * x.exp()
+    } else {
+        1.0
+    }
+}
+
+// self normalising, meant to be used with lecun initialisation
+pub fn selu(x: f32) -> f32 {
+    SELU_SCALE * elu(x, SELU_ALPHA)
+}
+
+pub fn selu_derivative(x: f32) -> f32 {
+    SELU_SCALE * elu_derivative(x, SELU_ALPHA)
+}
+
+// tanh approximation of x * Φ(x)
+pub fn gelu(x: f32) -> f32 {
+    0.5 * x * (1.0 + gelu_inner(x).tanh())
+}
+
+pub fn gelu_derivative(x: f32) -> f32 {
+    let t = gelu_inner(x).tanh();
+    let inner_derivative = (2.0 / PI).sqrt() * (1.0 + 3.0 * 0.044_715 * x * x);
+    0.5 * (1.0 + t) + 0.5 * x * (1.0 - t * t) * inner_derivative
+}
+
+fn gelu_inner(x: f32) -> f32 {
+    (2.0 / PI).sqrt() * (x + 0.044_715 * x.powi(3))
+}
+
+pub fn softplus(x: f32) -> f32 {
+    // ln(1 + e^x) without overflowing for large x
+    x.max(0.0) + (-x.abs()).exp().ln_1p()
+}
+
+pub fn softplus_derivative(x: f32) -> f32 {
+    sigmoid(x)
+}
+
+pub fn mish(x: f32) -> f32 {
+    x * softplus(x).tanh()
+}
+
+pub fn mish_derivative(x: f32) -> f32 {
+    let t = softplus(x).tanh();
+    t + x * sigmoid(x) * (1.0 - t * t)
+}
+
 // should be used in when the depth of the network is > 40
 pub fn swish(x: f32) -> f32 {
     x * sigmoid(x)
 }
 
+pub fn swish_derivative(x: f32) -> f32 {
+    let s = sigmoid(x);
+    s + x * s * (1.0 - s)
+}
+
+// piecewise linear approximation of the sigmoid
+pub fn hard_sigmoid(x: f32) -> f32 {
+    (0.2 * x + 0.5).clamp(0.0, 1.0)
+}
+
+pub fn hard_sigmoid_derivative(x: f32) -> f32 {
+    if x > -2.5 && x < 2.5 {
+        0.2
+    } else {
+        0.0
+    }
+}
+
+// normalises every row into a probability distribution
+pub fn softmax(x: &Array2<f32>) -> Array2<f32> {
+    let mut out = x.clone();
+    for mut row in out.axis_iter_mut(Axis(0)) {
+        // shift by the maximum so exp cannot overflow
+        let max = row.fold(f32::NEG_INFINITY, |m, v| m.max(*v));
+        row.mapv_inplace(|v| (v - max).exp());
+        let sum = row.sum();
+        row /= sum;
+    }
+    out
+}
+
 pub fn nop(x: f32) -> f32 {
     x
 }
 
-pub fn nop_derivative(x: f32) -> f32 {
+pub fn nop_derivative(_x: f32) -> f32 {
     1.0
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Activation {
+    Sigmoid,
+    Tanh,
+    Relu,
+    LeakyRelu(f32),
+    Elu(f32),
+    Selu,
+    Gelu,
+    Softplus,
+    Mish,
+    Swish,
+    HardSigmoid,
+    Identity,
+    // normalises over the whole layer, see activate and backpropagate
+    Softmax,
+}
+
+impl Activation {
+    // apply the activation to a (batch_size, layer_size) matrix of pre-activations
+    pub fn activate(&self, x: &Array2<f32>) -> Array2<f32> {
+        match self {
+            Activation::Softmax => softmax(x),
+            _ => x.mapv(|v| self.function(v)),
+        }
+    }
+
+    // turn the gradient with respect to the outputs into the gradient with respect to the
+    // pre-activations x, outputs are the values returned by activate
+    pub fn backpropagate(&self, x: &Array2<f32>, outputs: &Array2<f32>, gradient: &Array2<f32>) -> Array2<f32> {
+        match self {
+            Activation::Softmax => {
+                // jacobian vector product: s * (g - sum(g * s))
+                let dot = (gradient * outputs).sum_axis(Axis(1)).insert_axis(Axis(1));
+                outputs * &(gradient - &dot)
+            }
+            _ => gradient * &x.mapv(|v| self.derivative(v)),
+        }
+    }
+
+    // element wise softmax behaves like a layer with a single neuron
+    pub fn function(&self, x: f32) -> f32 {
+        match *self {
+            Activation::Sigmoid => sigmoid(x),
+            Activation::Tanh => tanh(x),
+            Activation::Relu => relu(x),
+            Activation::LeakyRelu(alpha) => leaky_relu(x, alpha),
+            Activation::Elu(alpha) => elu(x, alpha),
+            Activation::Selu => selu(x),
+            Activation::Gelu => gelu(x),
+            Activation::Softplus => softplus(x),
+            Activation::Mish => mish(x),
+            Activation::Swish => swish(x),
+            Activation::HardSigmoid => hard_sigmoid(x),
+            Activation::Identity => nop(x),
+            Activation::Softmax => 1.0,
+        }
+    }
+
+    pub fn derivative(&self, x: f32) -> f32 {
+        match *self {
+            Activation::Sigmoid => sigmoid_derivative(x),
+            Activation::Tanh => tanh_derivative(x),
+            Activation::Relu => relu_derivative(x),
+            Activation::LeakyRelu(alpha) => leaky_relu_derivative(x, alpha),
+            Activation::Elu(alpha) => elu_derivative(x, alpha),
+            Activation::Selu => selu_derivative(x),
+            Activation::Gelu => gelu_derivative(x),
+            Activation::Softplus => softplus_derivative(x),
+            Activation::Mish => mish_derivative(x),
+            Activation::Swish => swish_derivative(x),
+            Activation::HardSigmoid => hard_sigmoid_derivative(x),
+            Activation::Identity => nop_derivative(x),
+            Activation::Softmax => 0.0,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Activation::Sigmoid => "sigmoid",
+            Activation::Tanh => "tanh",
+            Activation::Relu => "relu",
+            Activation::LeakyRelu(_) => "leaky_relu",
+            Activation::Elu(_) => "elu",
+            Activation::Selu => "selu",
+            Activation::Gelu => "gelu",
+            Activation::Softplus => "softplus",
+            Activation::Mish => "mish",
+            Activation::Swish => "swish",
+            Activation::HardSigmoid => "hard_sigmoid",
+            Activation::Identity => "identity",
+            Activation::Softmax => "softmax",
+        }
+    }
+}
+
+// formats as the name followed by the parameter if there is one, e.g. leaky_relu(0.01)
+impl fmt::Display for Activation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Activation::LeakyRelu(alpha) | Activation::Elu(alpha) => write!(f, "{}({})", self.name(), alpha),
+            _ => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+impl FromStr for Activation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, param) = match s.trim().split_once('(') {
+            Some((name, rest)) => {
+                let param = rest
+                    .strip_suffix(')')
+                    .and_then(|p| p.trim().parse::<f32>().ok())
+                    .ok_or_else(|| format!("invalid activation parameter: {}", s))?;
+                (name, Some(param))
+            }
+            None => (s.trim(), None),
+        };
+
+        match (name, param) {
+            ("sigmoid", None) => Ok(Activation::Sigmoid),
+            ("tanh", None) => Ok(Activation::Tanh),
+            ("relu", None) => Ok(Activation::Relu),
+            ("leaky_relu", Some(alpha)) => Ok(Activation::LeakyRelu(alpha)),
+            ("elu", Some(alpha)) => Ok(Activation::Elu(alpha)),
+            ("selu", None) => Ok(Activation::Selu),
+            ("gelu", None) => Ok(Activation::Gelu),
+            ("softplus", None) => Ok(Activation::Softplus),
+            ("mish", None) => Ok(Activation::Mish),
+            ("swish", None) => Ok(Activation::Swish),
+            ("hard_sigmoid", None) => Ok(Activation::HardSigmoid),
+            ("identity", None) => Ok(Activation::Identity),
+            ("softmax", None) => Ok(Activation::Softmax),
+            _ => Err(format!("unknown activation: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+
+    const ALL: [Activation; 12] = [
+        Activation::Sigmoid,
+        Activation::Tanh,
+        Activation::Relu,
+        Activation::LeakyRelu(0.01),
+        Activation::Elu(1.0),
+        Activation::Selu,
+        Activation::Gelu,
+        Activation::Softplus,
+        Activation::Mish,
+        Activation::Swish,
+        Activation::HardSigmoid,
+        Activation::Identity,
+    ];
+
+    #[test]
+    fn derivative_test() {
+        let eps = 1e-3;
+        for activation in ALL {
+            // stay clear of the kinks at 0 and ±2.5
+            for x in [-3.1, -1.7, -0.4, 0.3, 1.2, 2.8] {
+                let numeric = (activation.function(x + eps) - activation.function(x - eps)) / (2.0 * eps);
+                let analytic = activation.derivative(x);
+                assert!(
+                    (numeric - analytic).abs() < 1e-2,
+                    "{} at {}: {} != {}",
+                    activation,
+                    x,
+                    numeric,
+                    analytic
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn name_test() {
+        for activation in ALL {
+            assert_eq!(Ok(activation), activation.to_string().parse());
+        }
+        assert_eq!("leaky_relu(0.2)", Activation::LeakyRelu(0.2).to_string());
+        assert!("leaky_relu".parse::<Activation>().is_err());
+        assert!("unknown".parse::<Activation>().is_err());
+        assert_eq!(Ok(Activation::Softmax), "softmax".parse());
+    }
+
+    #[test]
+    fn softmax_test() {
+        let x = array![[1.0, 2.0, 3.0], [1000.0, 1000.0, 1000.0]];
+        let s = Activation::Softmax.activate(&x);
+        for row in s.rows() {
+            assert!((row.sum() - 1.0).abs() < 1e-6);
+        }
+        assert!((s[[0, 2]] - 0.665_240_9).abs() < 1e-6);
+        assert!((s[[1, 0]] - 1.0 / 3.0).abs() < 1e-6);
+
+        // compare the jacobian vector product with finite differences
+        let x = array![[0.5, -1.0, 2.0]];
+        let g = array![[0.3, -0.2, 0.7]];
+        let analytic = Activation::Softmax.backpropagate(&x, &Activation::Softmax.activate(&x), &g);
+        let eps = 1e-2;
+        for i in 0..3 {
+            let mut plus = x.clone();
+            plus[[0, i]] += eps;
+            let mut minus = x.clone();
+            minus[[0, i]] -= eps;
+            let numeric = ((softmax(&plus) - softmax(&minus)) * &g).sum() / (2.0 * eps);
+            assert!((numeric - analytic[[0, i]]).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn softplus_test() {
+        assert_eq!(100.0, softplus(100.0));
+        assert!((softplus(0.0) - 2.0f32.ln()).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,359 @@
+use ndarray::prelude::*;
+use rand::Rng;
+
+use super::activation_functions::Activation;
+use super::conv::{Conv1D, Conv2D, Flatten};
+use super::dropout::Dropout;
+use super::error::Error;
+use super::layer::{ActivationLayer, DenseGradients, HiddenLayer, Layer};
+use super::neuron::Neuron;
+use super::normalization::{BatchNorm, LayerNorm, NormGradients, RmsNorm};
+use super::optimizers::Optimizer;
+use super::pooling::{AvgPool2D, GlobalAveragePool1D, GlobalMaxPool1D, MaxPool2D};
+
+// a layer between the input layer and the output layer
+#[derive(Debug, Clone)]
+pub enum Block {
+    Dense(HiddenLayer),
+    Dropout(Dropout),
+    Activation(ActivationLayer),
+    BatchNorm(BatchNorm),
+    LayerNorm(LayerNorm),
+    RmsNorm(RmsNorm),
+    Conv2D(Conv2D),
+    MaxPool2D(MaxPool2D),
+    AvgPool2D(AvgPool2D),
+    Flatten(Flatten),
+    Conv1D(Conv1D),
+    GlobalAveragePool1D(GlobalAveragePool1D),
+    GlobalMaxPool1D(GlobalMaxPool1D),
+}
+
+// gradients of one layer, one array per trainable parameter
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockGradients {
+    Dense(DenseGradients),
+    Norm(NormGradients),
+    // the kernels and biases of a convolution, laid out like those of a dense layer
+    Conv(DenseGradients),
+    // a layer without parameters
+    Empty,
+}
+
+impl BlockGradients {
+    // the gradients in the order the layer hands its parameters to the optimizer
+    pub fn arrays(&self) -> Vec<ArrayViewD<'_, f32>> {
+        match self {
+            BlockGradients::Dense(gradients) => gradients.arrays(),
+            BlockGradients::Norm(gradients) => gradients.arrays(),
+            BlockGradients::Conv(gradients) => gradients.arrays(),
+            BlockGradients::Empty => vec![],
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BlockGradients::Dense(_) => "dense",
+            BlockGradients::Norm(_) => "norm",
+            BlockGradients::Conv(_) => "conv",
+            BlockGradients::Empty => "empty",
+        }
+    }
+
+    pub fn arrays_mut(&mut self) -> Vec<ArrayViewMutD<'_, f32>> {
+        match self {
+            BlockGradients::Dense(gradients) => gradients.arrays_mut(),
+            BlockGradients::Norm(gradients) => gradients.arrays_mut(),
+            BlockGradients::Conv(gradients) => gradients.arrays_mut(),
+            BlockGradients::Empty => vec![],
+        }
+    }
+}
+
+impl Block {
+    pub fn as_dense(&self) -> Option<&HiddenLayer> {
+        match self {
+            Block::Dense(layer) => Some(layer),
+            _ => None,
+        }
+    }
+
+    pub fn as_dense_mut(&mut self) -> Option<&mut HiddenLayer> {
+        match self {
+            Block::Dense(layer) => Some(layer),
+            _ => None,
+        }
+    }
+
+    // training switches dropout on and batch normalisation to the statistics of the batch,
+    // rng draws the dropout masks
+    pub fn forward<R: Rng + ?Sized>(&mut self, inputs: &ArrayView2<f32>, training: bool, rng: &mut R) {
+        match self {
+            Block::Dense(layer) => layer.forward(inputs),
+            Block::Dropout(dropout) => dropout.forward(inputs, training, rng),
+            Block::Activation(layer) => layer.forward(inputs),
+            Block::BatchNorm(norm) => norm.forward(inputs, training),
+            Block::LayerNorm(norm) => norm.forward(inputs),
+            Block::RmsNorm(norm) => norm.forward(inputs),
+            Block::Conv2D(conv) => conv.forward(inputs),
+            Block::MaxPool2D(pool) => pool.forward(inputs),
+            Block::AvgPool2D(pool) => pool.forward(inputs),
+            Block::Flatten(flatten) => flatten.forward(inputs),
+            Block::Conv1D(conv) => conv.forward(inputs),
+            Block::GlobalAveragePool1D(pool) => pool.forward(inputs),
+            Block::GlobalMaxPool1D(pool) => pool.forward(inputs),
+        }
+    }
+
+    // gradients of the last forward pass for the deltas of the outputs of the layer,
+    // and the deltas of its inputs for the layer before it
+    pub fn backward(&self, prev_values: &ArrayView2<f32>, deltas: &Array2<f32>) -> (BlockGradients, Array2<f32>) {
+        match self {
+            Block::Dense(layer) => {
+                let deltas = layer
+                    .activation
+                    .backpropagate(&layer.input_values, &layer.output_values, deltas);
+                let gradients = layer.gradients(prev_values, &deltas);
+                (BlockGradients::Dense(gradients), layer.propagate(&deltas))
+            }
+            Block::Dropout(dropout) => (BlockGradients::Empty, dropout.propagate(deltas)),
+            Block::Activation(layer) => (BlockGradients::Empty, layer.propagate(deltas)),
+            Block::BatchNorm(norm) => (BlockGradients::Norm(norm.gradients(deltas)), norm.propagate(deltas)),
+            Block::LayerNorm(norm) => (BlockGradients::Norm(norm.gradients(deltas)), norm.propagate(deltas)),
+            Block::RmsNorm(norm) => (BlockGradients::Norm(norm.gradients(deltas)), norm.propagate(deltas)),
+            Block::Conv2D(conv) => {
+                let deltas = conv
+                    .activation
+                    .backpropagate(&conv.input_values, &conv.output_values, deltas);
+                (BlockGradients::Conv(conv.gradients(&deltas)), conv.propagate(&deltas))
+            }
+            Block::MaxPool2D(pool) => (BlockGradients::Empty, pool.propagate(deltas)),
+            Block::AvgPool2D(pool) => (BlockGradients::Empty, pool.propagate(deltas)),
+            Block::Flatten(flatten) => (BlockGradients::Empty, flatten.propagate(deltas)),
+            Block::Conv1D(conv) => {
+                let deltas = conv
+                    .activation
+                    .backpropagate(&conv.input_values, &conv.output_values, deltas);
+                (BlockGradients::Conv(conv.gradients(&deltas)), conv.propagate(&deltas))
+            }
+            Block::GlobalAveragePool1D(pool) => (BlockGradients::Empty, pool.propagate(deltas)),
+            Block::GlobalMaxPool1D(pool) => (BlockGradients::Empty, pool.propagate(deltas)),
+        }
+    }
+
+    // whether the gradients are of the kind backward returns for this block
+    pub fn accepts(&self, gradients: &BlockGradients) -> bool {
+        matches!(
+            (self, gradients),
+            (Block::Dense(_), BlockGradients::Dense(_))
+                | (Block::BatchNorm(_) | Block::LayerNorm(_) | Block::RmsNorm(_), BlockGradients::Norm(_))
+                | (Block::Conv2D(_) | Block::Conv1D(_), BlockGradients::Conv(_))
+                | (
+                    Block::Dropout(_)
+                        | Block::Activation(_)
+                        | Block::MaxPool2D(_)
+                        | Block::AvgPool2D(_)
+                        | Block::Flatten(_)
+                        | Block::GlobalAveragePool1D(_)
+                        | Block::GlobalMaxPool1D(_),
+                    BlockGradients::Empty,
+                )
+        )
+    }
+
+    // layer is the index of the block, errors count it after the input layer like the network does
+    pub fn apply(&mut self, gradients: &BlockGradients, optimizer: &mut dyn Optimizer, layer: usize) -> Result<(), Error> {
+        if !self.accepts(gradients) {
+            return Err(Error::InvalidLayer {
+                layer: layer + 1,
+                message: format!("{} gradients do not match a {} layer", gradients.kind(), self.kind()),
+            });
+        }
+        match (self, gradients) {
+            (Block::Dense(dense), BlockGradients::Dense(gradients)) => dense.apply(gradients, optimizer, layer),
+            (Block::BatchNorm(norm), BlockGradients::Norm(gradients)) => norm.apply(gradients, optimizer, layer),
+            (Block::LayerNorm(norm), BlockGradients::Norm(gradients)) => norm.apply(gradients, optimizer, layer),
+            (Block::RmsNorm(norm), BlockGradients::Norm(gradients)) => norm.apply(gradients, optimizer, layer),
+            (Block::Conv2D(conv), BlockGradients::Conv(gradients)) => conv.apply(gradients, optimizer, layer),
+            (Block::Conv1D(conv), BlockGradients::Conv(gradients)) => conv.apply(gradients, optimizer, layer),
+            // layers without parameters
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // the trainable parameters in the order of BlockGradients::arrays
+    pub(crate) fn params_mut(&mut self) -> Vec<ArrayViewMutD<'_, f32>> {
+        match self {
+            Block::Dense(layer) => layer.params_mut(),
+            Block::BatchNorm(norm) => norm.params_mut(),
+            Block::LayerNorm(norm) => norm.params_mut(),
+            Block::RmsNorm(norm) => norm.params_mut(),
+            Block::Conv2D(conv) => conv.params_mut(),
+            Block::Conv1D(conv) => conv.params_mut(),
+            _ => vec![],
+        }
+    }
+
+    // the l1 and l2 penalties of the layer
+    pub fn penalty(&self) -> f32 {
+        match self {
+            Block::Dense(layer) => layer.regularizer.penalty(&layer.weights, &layer.biases),
+            Block::Conv2D(conv) => conv.regularizer.penalty(&conv.weights, &conv.biases),
+            Block::Conv1D(conv) => conv.regularizer.penalty(&conv.weights, &conv.biases),
+            _ => 0.0,
+        }
+    }
+
+    // the number of values per sample the layer reads from the layer before it
+    pub fn input_len(&self) -> usize {
+        let (channels, height, width) = match self {
+            Block::Dense(layer) => return layer.weights.nrows(),
+            Block::Conv2D(conv) => conv.input_shape,
+            Block::MaxPool2D(pool) => pool.window.input_shape,
+            Block::AvgPool2D(pool) => pool.window.input_shape,
+            Block::Flatten(flatten) => flatten.input_shape,
+            Block::Conv1D(conv) => (conv.input_shape.0, 1, conv.input_shape.1),
+            Block::GlobalAveragePool1D(pool) => (pool.input_shape.0, 1, pool.input_shape.1),
+            Block::GlobalMaxPool1D(pool) => (pool.input_shape.0, 1, pool.input_shape.1),
+            _ => return self.len(),
+        };
+        channels * height * width
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Block::Dense(_) => "dense",
+            Block::Dropout(_) => "dropout",
+            Block::Activation(_) => "activation",
+            Block::BatchNorm(_) => "batch_norm",
+            Block::LayerNorm(_) => "layer_norm",
+            Block::RmsNorm(_) => "rms_norm",
+            Block::Conv2D(_) => "conv2d",
+            Block::MaxPool2D(_) => "max_pool2d",
+            Block::AvgPool2D(_) => "avg_pool2d",
+            Block::Flatten(_) => "flatten",
+            Block::Conv1D(_) => "conv1d",
+            Block::GlobalAveragePool1D(_) => "global_average_pool1d",
+            Block::GlobalMaxPool1D(_) => "global_max_pool1d",
+        }
+    }
+
+    fn layer(&self) -> &dyn Layer {
+        match self {
+            Block::Dense(layer) => layer,
+            Block::Dropout(dropout) => dropout,
+            Block::Activation(layer) => layer,
+            Block::BatchNorm(norm) => norm,
+            Block::LayerNorm(norm) => norm,
+            Block::RmsNorm(norm) => norm,
+            Block::Conv2D(conv) => conv,
+            Block::MaxPool2D(pool) => pool,
+            Block::AvgPool2D(pool) => pool,
+            Block::Flatten(flatten) => flatten,
+            Block::Conv1D(conv) => conv,
+            Block::GlobalAveragePool1D(pool) => pool,
+            Block::GlobalMaxPool1D(pool) => pool,
+        }
+    }
+}
+
+impl From<HiddenLayer> for Block {
+    fn from(layer: HiddenLayer) -> Self {
+        Block::Dense(layer)
+    }
+}
+
+impl From<Dropout> for Block {
+    fn from(dropout: Dropout) -> Self {
+        Block::Dropout(dropout)
+    }
+}
+
+impl From<ActivationLayer> for Block {
+    fn from(layer: ActivationLayer) -> Self {
+        Block::Activation(layer)
+    }
+}
+
+impl From<BatchNorm> for Block {
+    fn from(norm: BatchNorm) -> Self {
+        Block::BatchNorm(norm)
+    }
+}
+
+impl From<LayerNorm> for Block {
+    fn from(norm: LayerNorm) -> Self {
+        Block::LayerNorm(norm)
+    }
+}
+
+impl From<RmsNorm> for Block {
+    fn from(norm: RmsNorm) -> Self {
+        Block::RmsNorm(norm)
+    }
+}
+
+impl From<Conv2D> for Block {
+    fn from(conv: Conv2D) -> Self {
+        Block::Conv2D(conv)
+    }
+}
+
+impl From<MaxPool2D> for Block {
+    fn from(pool: MaxPool2D) -> Self {
+        Block::MaxPool2D(pool)
+    }
+}
+
+impl From<AvgPool2D> for Block {
+    fn from(pool: AvgPool2D) -> Self {
+        Block::AvgPool2D(pool)
+    }
+}
+
+impl From<Flatten> for Block {
+    fn from(flatten: Flatten) -> Self {
+        Block::Flatten(flatten)
+    }
+}
+
+impl From<Conv1D> for Block {
+    fn from(conv: Conv1D) -> Self {
+        Block::Conv1D(conv)
+    }
+}
+
+impl From<GlobalAveragePool1D> for Block {
+    fn from(pool: GlobalAveragePool1D) -> Self {
+        Block::GlobalAveragePool1D(pool)
+    }
+}
+
+impl From<GlobalMaxPool1D> for Block {
+    fn from(pool: GlobalMaxPool1D) -> Self {
+        Block::GlobalMaxPool1D(pool)
+    }
+}
+
+impl Layer for Block {
+    fn len_weights(&self) -> u32 {
+        self.layer().len_weights()
+    }
+
+    fn batch_values(&self) -> ArrayView2<'_, f32> {
+        self.layer().batch_values()
+    }
+
+    fn len(&self) -> usize {
+        self.layer().len()
+    }
+
+    fn get(&self, index: usize) -> Option<Neuron> {
+        self.layer().get(index)
+    }
+
+    fn get_activation(&self) -> Activation {
+        self.layer().get_activation()
+    }
+}
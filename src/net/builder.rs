@@ -0,0 +1,594 @@
+use std::sync::Arc;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use super::{
+    activation_functions::Activation,
+    block::Block,
+    conv::{Conv1D, Conv2D, Flatten, Padding, Shape3},
+    dropout::Dropout,
+    error::Error,
+    layer::{ActivationLayer, HiddenLayer, InputLayer, Layer, OutputLayer},
+    loss_functions::Loss,
+    network::Network,
+    normalization::{BatchNorm, LayerNorm, RmsNorm},
+    pooling::{AvgPool2D, GlobalAveragePool1D, GlobalMaxPool1D, MaxPool2D},
+    regularizers::Regularizer,
+    weight_functions::Initializer,
+};
+
+#[derive(Debug, Clone)]
+struct DenseSpec {
+    layer_size: u32,
+    activation: Activation,
+    bias: bool,
+    initializer: Initializer,
+    regularizer: Regularizer,
+}
+
+#[derive(Debug, Clone)]
+struct Conv2DSpec {
+    filters: usize,
+    kernel: (usize, usize),
+    stride: (usize, usize),
+    padding: Padding,
+    activation: Activation,
+    bias: bool,
+    initializer: Initializer,
+    regularizer: Regularizer,
+}
+
+#[derive(Debug, Clone)]
+struct Conv1DSpec {
+    filters: usize,
+    kernel: usize,
+    stride: usize,
+    dilation: usize,
+    padding: Padding,
+    activation: Activation,
+    bias: bool,
+    initializer: Initializer,
+    regularizer: Regularizer,
+}
+
+#[derive(Debug, Clone)]
+enum LayerSpec {
+    Dense(DenseSpec),
+    Dropout(f32),
+    Activation(Activation),
+    BatchNorm,
+    LayerNorm,
+    RmsNorm,
+    // not a layer, the shape the following image layers read the values in
+    Reshape(Shape3),
+    Conv2D(Conv2DSpec),
+    MaxPool2D((usize, usize), (usize, usize)),
+    AvgPool2D((usize, usize), (usize, usize)),
+    Flatten,
+    Conv1D(Conv1DSpec),
+    GlobalAveragePool1D,
+    GlobalMaxPool1D,
+}
+
+// builds a network layer by layer, the last layer must be dense and becomes the output layer
+//
+//  NetworkBuilder::new(2)
+//      .dense(3, Activation::Sigmoid)
+//      .dropout(0.2)
+//      .dense(1, Activation::Identity)
+//      .build()
+//
+// a dense layer with the identity activation followed by batch_norm and activation is a dense layer
+// with its pre-activations normalised
+//
+// image layers need the shape of their inputs, reshape gives it and every image layer passes its own on
+//
+//  NetworkBuilder::new(28 * 28)
+//      .reshape(1, 28, 28)
+//      .conv2d(8, (3, 3), (1, 1), Padding::Same, Activation::Relu)
+//      .max_pool2d((2, 2), (2, 2))
+//      .flatten()
+//      .dense(10, Activation::Softmax)
+//      .build()
+//
+// sequences are images of height 1, reshape1d gives their (channels, length)
+//
+//  NetworkBuilder::new(3 * 128)
+//      .reshape1d(3, 128)
+//      .conv1d(16, 3, 1, 2, Padding::Causal, Activation::Relu)
+//      .global_average_pool1d()
+//      .dense(1, Activation::Sigmoid)
+//      .build()
+#[derive(Debug, Clone)]
+pub struct NetworkBuilder {
+    inputs: u32,
+    layers: Vec<LayerSpec>,
+    bias: bool,
+    initializer: Initializer,
+    regularizer: Regularizer,
+    loss: Option<Arc<dyn Loss>>,
+    seed: Option<u64>,
+}
+
+impl NetworkBuilder {
+    pub fn new(inputs: u32) -> Self {
+        Self {
+            inputs,
+            layers: vec![],
+            bias: true,
+            initializer: Initializer::default(),
+            regularizer: Regularizer::default(),
+            loss: None,
+            seed: None,
+        }
+    }
+
+    // append a fully connected layer, its fan-in is the size of the previous layer
+    pub fn dense(mut self, layer_size: u32, activation: Activation) -> Self {
+        self.layers.push(LayerSpec::Dense(DenseSpec {
+            layer_size,
+            activation,
+            bias: self.bias,
+            initializer: self.initializer,
+            regularizer: self.regularizer,
+        }));
+        self
+    }
+
+    // append inverted dropout with the given rate in [0, 1), active only in training mode
+    pub fn dropout(mut self, rate: f32) -> Self {
+        self.layers.push(LayerSpec::Dropout(rate));
+        self
+    }
+
+    // append an activation of the values of the previous layer
+    pub fn activation(mut self, activation: Activation) -> Self {
+        self.layers.push(LayerSpec::Activation(activation));
+        self
+    }
+
+    // append batch normalisation of the values of the previous layer, see BatchNorm
+    pub fn batch_norm(mut self) -> Self {
+        self.layers.push(LayerSpec::BatchNorm);
+        self
+    }
+
+    // append layer normalisation of every sample, see LayerNorm
+    pub fn layer_norm(mut self) -> Self {
+        self.layers.push(LayerSpec::LayerNorm);
+        self
+    }
+
+    // append rms normalisation of every sample, see RmsNorm
+    pub fn rms_norm(mut self) -> Self {
+        self.layers.push(LayerSpec::RmsNorm);
+        self
+    }
+
+    // read the values of the previous layer as (channels, height, width) images,
+    // adds no layer and must match the size of the previous layer
+    pub fn reshape(mut self, channels: usize, height: usize, width: usize) -> Self {
+        self.layers.push(LayerSpec::Reshape((channels, height, width)));
+        self
+    }
+
+    // read the values of the previous layer as (channels, length) sequences, see reshape
+    pub fn reshape1d(self, channels: usize, length: usize) -> Self {
+        self.reshape(channels, 1, length)
+    }
+
+    // append a 1d convolution with filters kernels of kernel taps, dilation positions apart, see Conv1D
+    pub fn conv1d(
+        mut self,
+        filters: usize,
+        kernel: usize,
+        stride: usize,
+        dilation: usize,
+        padding: Padding,
+        activation: Activation,
+    ) -> Self {
+        self.layers.push(LayerSpec::Conv1D(Conv1DSpec {
+            filters,
+            kernel,
+            stride,
+            dilation,
+            padding,
+            activation,
+            bias: self.bias,
+            initializer: self.initializer,
+            regularizer: self.regularizer,
+        }));
+        self
+    }
+
+    // append the mean of every channel of a sequence
+    pub fn global_average_pool1d(mut self) -> Self {
+        self.layers.push(LayerSpec::GlobalAveragePool1D);
+        self
+    }
+
+    // append the maximum of every channel of a sequence
+    pub fn global_max_pool1d(mut self) -> Self {
+        self.layers.push(LayerSpec::GlobalMaxPool1D);
+        self
+    }
+
+    // append a 2d convolution with filters kernels of the given (height, width), see Conv2D
+    pub fn conv2d(
+        mut self,
+        filters: usize,
+        kernel: (usize, usize),
+        stride: (usize, usize),
+        padding: Padding,
+        activation: Activation,
+    ) -> Self {
+        self.layers.push(LayerSpec::Conv2D(Conv2DSpec {
+            filters,
+            kernel,
+            stride,
+            padding,
+            activation,
+            bias: self.bias,
+            initializer: self.initializer,
+            regularizer: self.regularizer,
+        }));
+        self
+    }
+
+    // append the maximum of every (height, width) window, moved by stride
+    pub fn max_pool2d(mut self, pool: (usize, usize), stride: (usize, usize)) -> Self {
+        self.layers.push(LayerSpec::MaxPool2D(pool, stride));
+        self
+    }
+
+    // append the mean of every (height, width) window, moved by stride
+    pub fn avg_pool2d(mut self, pool: (usize, usize), stride: (usize, usize)) -> Self {
+        self.layers.push(LayerSpec::AvgPool2D(pool, stride));
+        self
+    }
+
+    // append the end of the image layers, the layers after it see plain features
+    pub fn flatten(mut self) -> Self {
+        self.layers.push(LayerSpec::Flatten);
+        self
+    }
+
+    // whether the dense and convolution layers added after this call get a bias vector, defaults to true
+    pub fn bias(mut self, bias: bool) -> Self {
+        self.bias = bias;
+        self
+    }
+
+    // how the weights of the dense and convolution layers added after this call are drawn, defaults to glorot uniform
+    pub fn initializer(mut self, initializer: Initializer) -> Self {
+        self.initializer = initializer;
+        self
+    }
+
+    // regularisation of the dense and convolution layers added after this call, defaults to none
+    pub fn regularizer(mut self, regularizer: Regularizer) -> Self {
+        self.regularizer = regularizer;
+        self
+    }
+
+    // the loss minimised by the backward pass, see Network::new for the default
+    pub fn loss<L: Loss + 'static>(mut self, loss: L) -> Self {
+        self.loss = Some(Arc::new(loss));
+        self
+    }
+
+    // seeds the initial weights and the rng the network keeps for training,
+    // two networks built with the same seed train bit-identically
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn build(self) -> Result<Network, Error> {
+        let mut rng = match self.seed {
+            Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+            None => ChaCha8Rng::from_entropy(),
+        };
+        self.build_with_rng(&mut rng)
+    }
+
+    // draws the initial weights from rng and then seeds the rng of the network from it,
+    // so the same seeded rng builds the same network
+    pub fn build_with_rng<R: Rng + ?Sized>(self, rng: &mut R) -> Result<Network, Error> {
+        if self.inputs == 0 {
+            return Err(Error::EmptyLayer { layer: 0 });
+        }
+        let (output, hidden) = self.layers.split_last().ok_or(Error::EmptyNetwork)?;
+        // reshapes add no layer and do not count
+        let numbered = || self.layers.iter().filter(|l| !matches!(l, LayerSpec::Reshape(_)));
+        let empty = |l: &LayerSpec| matches!(l, LayerSpec::Dense(spec) if spec.layer_size == 0);
+        if let Some(n) = numbered().position(empty) {
+            return Err(Error::EmptyLayer { layer: n + 1 });
+        }
+        let LayerSpec::Dense(output) = output else {
+            return Err(Error::InvalidLayer {
+                layer: numbered().count(),
+                message: "the output layer must be dense".to_string(),
+            });
+        };
+
+        let input_layer = InputLayer::new(self.inputs);
+        let mut layers: Vec<Block> = Vec::with_capacity(hidden.len());
+        // the (channels, height, width) of the values of the previous layer, if it has one
+        let mut shape: Option<Shape3> = None;
+        for spec in hidden {
+            let prev_layer: &dyn Layer = match layers.last() {
+                Some(prev) => prev,
+                None => &input_layer,
+            };
+            let layer = layers.len() + 1;
+            let image = || {
+                shape.ok_or_else(|| Error::InvalidLayer {
+                    layer,
+                    message: "image layers need the shape of their inputs, see NetworkBuilder::reshape".to_string(),
+                })
+            };
+            let sequence = || match image()? {
+                (channels, 1, length) => Ok((channels, length)),
+                shape => Err(Error::InvalidLayer {
+                    layer,
+                    message: format!("{:?} are no (channels, 1, length) sequences, see NetworkBuilder::reshape1d", shape),
+                }),
+            };
+            let block = match spec {
+                LayerSpec::Dense(spec) => {
+                    let mut layer = HiddenLayer::with_initializer(
+                        spec.layer_size,
+                        spec.bias,
+                        spec.activation,
+                        spec.initializer,
+                        rng,
+                        prev_layer,
+                    );
+                    layer.regularizer = spec.regularizer;
+                    Block::Dense(layer)
+                }
+                LayerSpec::Dropout(rate) => Block::Dropout(Dropout::new(*rate, prev_layer)),
+                LayerSpec::Activation(activation) => Block::Activation(ActivationLayer::new(*activation, prev_layer)),
+                LayerSpec::BatchNorm => Block::BatchNorm(BatchNorm::new(prev_layer)),
+                LayerSpec::LayerNorm => Block::LayerNorm(LayerNorm::new(prev_layer)),
+                LayerSpec::RmsNorm => Block::RmsNorm(RmsNorm::new(prev_layer)),
+                LayerSpec::Reshape(to) => {
+                    let (channels, height, width) = *to;
+                    if channels * height * width != prev_layer.len() {
+                        return Err(Error::ShapeMismatch {
+                            layer,
+                            expected: prev_layer.len(),
+                            found: channels * height * width,
+                        });
+                    }
+                    shape = Some(*to);
+                    continue;
+                }
+                LayerSpec::Conv2D(spec) => {
+                    let mut conv = Conv2D::new(image()?, spec.filters, spec.kernel, spec.stride, spec.padding, spec.activation);
+                    conv.weights = spec.initializer.init(conv.weights.nrows(), spec.filters, rng);
+                    conv.bias = spec.bias;
+                    conv.regularizer = spec.regularizer;
+                    Block::Conv2D(conv)
+                }
+                LayerSpec::MaxPool2D(pool, stride) => Block::MaxPool2D(MaxPool2D::new(image()?, *pool, *stride)),
+                LayerSpec::AvgPool2D(pool, stride) => Block::AvgPool2D(AvgPool2D::new(image()?, *pool, *stride)),
+                LayerSpec::Flatten => Block::Flatten(Flatten::new(image()?)),
+                LayerSpec::Conv1D(spec) => {
+                    let mut conv = Conv1D::new(
+                        sequence()?,
+                        spec.filters,
+                        spec.kernel,
+                        spec.stride,
+                        spec.dilation,
+                        spec.padding,
+                        spec.activation,
+                    );
+                    conv.weights = spec.initializer.init(conv.weights.nrows(), spec.filters, rng);
+                    conv.bias = spec.bias;
+                    conv.regularizer = spec.regularizer;
+                    Block::Conv1D(conv)
+                }
+                LayerSpec::GlobalAveragePool1D => Block::GlobalAveragePool1D(GlobalAveragePool1D::new(sequence()?)),
+                LayerSpec::GlobalMaxPool1D => Block::GlobalMaxPool1D(GlobalMaxPool1D::new(sequence()?)),
+            };
+            // the elementwise layers keep the shape, the others end it or start a new one
+            shape = match &block {
+                Block::Conv2D(conv) => Some(conv.output_shape),
+                Block::MaxPool2D(pool) => Some(pool.window.output_shape()),
+                Block::AvgPool2D(pool) => Some(pool.window.output_shape()),
+                Block::Conv1D(conv) => Some((conv.output_shape.0, 1, conv.output_shape.1)),
+                Block::Dense(_) | Block::Flatten(_) | Block::GlobalAveragePool1D(_) | Block::GlobalMaxPool1D(_) => None,
+                _ => shape,
+            };
+            layers.push(block);
+        }
+
+        let prev_layer: &dyn Layer = match layers.last() {
+            Some(prev) => prev,
+            None => &input_layer,
+        };
+        let mut output_layer = OutputLayer::with_initializer(
+            output.layer_size,
+            output.bias,
+            output.activation,
+            output.initializer,
+            rng,
+            prev_layer,
+        );
+        output_layer.regularizer = output.regularizer;
+
+        let rng = ChaCha8Rng::from_rng(rng).expect("seeding from an rng cannot fail");
+        let mut network = Network::with_rng(input_layer, layers, output_layer, rng)?;
+        if let Some(loss) = self.loss {
+            network.set_shared_loss(loss);
+        }
+        Ok(network)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{array, Array2};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::net::loss_functions::Huber;
+
+    use super::*;
+
+    #[test]
+    fn build_test() {
+        let mut net = NetworkBuilder::new(3)
+            .bias(false)
+            .dense(5, Activation::Tanh)
+            .dense(4, Activation::Relu)
+            .dense(2, Activation::Identity)
+            .loss(Huber::new(0.5))
+            .build()
+            .unwrap();
+        assert_eq!("huber", net.loss().name());
+
+        let outputs = net.forward_batch(&array![[1.0, 2.0, 3.0], [0.0, 0.0, 0.0]]).unwrap();
+        assert_eq!((2, 2), outputs.dim());
+        // without biases a zero input can only produce zero outputs
+        assert_eq!(array![0.0, 0.0], outputs.row(1));
+    }
+
+    #[test]
+    fn initializer_test() {
+        let builder = NetworkBuilder::new(3)
+            .initializer(Initializer::HeNormal)
+            .dense(4, Activation::Relu)
+            .initializer(Initializer::Constant(0.25))
+            .dense(2, Activation::Sigmoid);
+        let a = builder.clone().build_with_rng(&mut StdRng::seed_from_u64(3)).unwrap();
+        let b = builder.build_with_rng(&mut StdRng::seed_from_u64(3)).unwrap();
+        assert_eq!(a.hidden_layers()[0].weights, b.hidden_layers()[0].weights);
+        assert_eq!(Array2::from_elem((4, 2), 0.25), a.output_layer().weights);
+    }
+
+    #[test]
+    fn build_error_test() {
+        assert_eq!(Err(Error::EmptyNetwork), NetworkBuilder::new(3).build().map(|_| ()));
+        assert_eq!(
+            Err(Error::EmptyLayer { layer: 0 }),
+            NetworkBuilder::new(0).dense(1, Activation::Identity).build().map(|_| ())
+        );
+        assert_eq!(
+            Err(Error::EmptyLayer { layer: 2 }),
+            NetworkBuilder::new(2)
+                .dense(3, Activation::Relu)
+                .dense(0, Activation::Relu)
+                .dense(1, Activation::Identity)
+                .build()
+                .map(|_| ())
+        );
+        assert!(matches!(
+            NetworkBuilder::new(2).dense(3, Activation::Relu).dropout(0.5).build(),
+            Err(Error::InvalidLayer { layer: 2, .. })
+        ));
+        assert!(matches!(
+            NetworkBuilder::new(2)
+                .dropout(1.0)
+                .dense(1, Activation::Identity)
+                .build(),
+            Err(Error::InvalidLayer { layer: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn image_test() {
+        let network = NetworkBuilder::new(2 * 6 * 6)
+            .reshape(2, 6, 6)
+            .conv2d(4, (3, 3), (1, 1), Padding::Valid, Activation::Relu)
+            .batch_norm()
+            .avg_pool2d((2, 2), (2, 2))
+            .flatten()
+            .dense(3, Activation::Softmax)
+            .build()
+            .unwrap();
+        let Block::Conv2D(conv) = &network.blocks()[0] else { unreachable!() };
+        assert_eq!((4, 4, 4), conv.output_shape);
+        assert_eq!((18, 4), conv.weights.dim());
+        // the batch normalisation keeps the shape for the pooling
+        let Block::AvgPool2D(pool) = &network.blocks()[2] else { unreachable!() };
+        assert_eq!((4, 2, 2), pool.window.output_shape());
+        assert_eq!((16, 3), network.output_layer().weights.dim());
+
+        // reshapes are not layers
+        assert_eq!(
+            Err(Error::ShapeMismatch { layer: 2, expected: 4, found: 8 }),
+            NetworkBuilder::new(2)
+                .dense(4, Activation::Relu)
+                .reshape(2, 2, 2)
+                .flatten()
+                .dense(1, Activation::Identity)
+                .build()
+                .map(|_| ())
+        );
+        // a dense layer ends the image
+        assert!(matches!(
+            NetworkBuilder::new(4)
+                .reshape(1, 2, 2)
+                .dense(4, Activation::Relu)
+                .max_pool2d((2, 2), (2, 2))
+                .dense(1, Activation::Identity)
+                .build(),
+            Err(Error::InvalidLayer { layer: 2, .. })
+        ));
+        assert!(matches!(
+            NetworkBuilder::new(4)
+                .reshape(1, 2, 2)
+                .conv2d(1, (3, 3), (1, 1), Padding::Valid, Activation::Relu)
+                .dense(1, Activation::Identity)
+                .build(),
+            Err(Error::InvalidLayer { layer: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn sequence_test() {
+        let network = NetworkBuilder::new(3 * 10)
+            .reshape1d(3, 10)
+            .conv1d(4, 3, 1, 2, Padding::Causal, Activation::Relu)
+            .conv1d(2, 3, 2, 1, Padding::Valid, Activation::Relu)
+            .global_max_pool1d()
+            .dense(1, Activation::Sigmoid)
+            .build()
+            .unwrap();
+        let Block::Conv1D(conv) = &network.blocks()[0] else { unreachable!() };
+        assert_eq!(((4, 10), (9, 4)), (conv.output_shape, conv.weights.dim()));
+        let Block::Conv1D(conv) = &network.blocks()[1] else { unreachable!() };
+        assert_eq!((2, 4), conv.output_shape);
+        assert_eq!((2, 1), network.output_layer().weights.dim());
+
+        // sequences have a height of 1
+        assert!(matches!(
+            NetworkBuilder::new(8)
+                .reshape(2, 2, 2)
+                .global_average_pool1d()
+                .dense(1, Activation::Identity)
+                .build(),
+            Err(Error::InvalidLayer { layer: 1, .. })
+        ));
+        assert!(matches!(
+            NetworkBuilder::new(8)
+                .reshape1d(2, 4)
+                .conv1d(1, 3, 1, 2, Padding::Valid, Activation::Relu)
+                .dense(1, Activation::Identity)
+                .build(),
+            Err(Error::InvalidLayer { layer: 1, .. })
+        ));
+        assert!(matches!(
+            NetworkBuilder::new(8)
+                .reshape1d(2, 4)
+                .conv1d(1, 0, 1, 1, Padding::Valid, Activation::Relu)
+                .dense(1, Activation::Identity)
+                .build(),
+            Err(Error::InvalidLayer { layer: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,105 @@
+use super::network::Network;
+use super::optimizers::Optimizer;
+use super::schedules::LrSchedule;
+use super::trainer::History;
+
+// returned by callbacks to end training early
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    Stop,
+}
+
+// state of the training loop when a callback fires
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metrics {
+    pub epoch: usize,
+    // batch index inside the epoch
+    pub batch: usize,
+    // batch index counted over all epochs
+    pub step: usize,
+    // loss of the batch on batch end, mean training loss of the epoch on epoch end
+    pub loss: f32,
+    pub validation_loss: Option<f32>,
+    pub learning_rate: f32,
+}
+
+// every hook has a default that does nothing
+pub trait Callback {
+    fn on_batch_start(&mut self, _network: &Network, _optimizer: &mut dyn Optimizer, _metrics: &Metrics) {}
+
+    fn on_batch_end(&mut self, _network: &Network, _metrics: &Metrics) -> Control {
+        Control::Continue
+    }
+
+    fn on_epoch_end(&mut self, _network: &Network, _optimizer: &mut dyn Optimizer, _metrics: &Metrics) -> Control {
+        Control::Continue
+    }
+
+    fn on_train_end(&mut self, _network: &Network, _history: &History) {}
+}
+
+// sets the learning rate of the optimizer from a schedule, stepped per epoch or per batch
+#[derive(Debug)]
+pub struct LrScheduler<S: LrSchedule> {
+    schedule: S,
+    per_batch: bool,
+}
+
+impl<S: LrSchedule> LrScheduler<S> {
+    // query the schedule with the epoch index before the first batch of every epoch
+    pub fn per_epoch(schedule: S) -> Self {
+        Self {
+            schedule,
+            per_batch: false,
+        }
+    }
+
+    // query the schedule with the global batch index before every batch
+    pub fn per_batch(schedule: S) -> Self {
+        Self {
+            schedule,
+            per_batch: true,
+        }
+    }
+}
+
+impl<S: LrSchedule> Callback for LrScheduler<S> {
+    fn on_batch_start(&mut self, _network: &Network, optimizer: &mut dyn Optimizer, metrics: &Metrics) {
+        if self.per_batch {
+            self.schedule.apply(optimizer, metrics.step);
+        } else if metrics.batch == 0 {
+            self.schedule.apply(optimizer, metrics.epoch);
+        }
+    }
+}
+
+// logs the losses of every epoch through tracing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Logger;
+
+impl Callback for Logger {
+    fn on_epoch_end(&mut self, _network: &Network, _optimizer: &mut dyn Optimizer, metrics: &Metrics) -> Control {
+        tracing::info!(
+            epoch = metrics.epoch,
+            loss = metrics.loss,
+            validation_loss = metrics.validation_loss,
+            learning_rate = metrics.learning_rate,
+            "epoch finished"
+        );
+        Control::Continue
+    }
+
+    fn on_train_end(&mut self, _network: &Network, history: &History) {
+        tracing::info!(epochs = history.epochs(), stopped_early = history.stopped_early, "training finished");
+    }
+}
+
+// calls the closure at the end of every epoch, e.g. for custom stopping rules
+pub struct OnEpochEnd<F: FnMut(&Network, &Metrics) -> Control>(pub F);
+
+impl<F: FnMut(&Network, &Metrics) -> Control> Callback for OnEpochEnd<F> {
+    fn on_epoch_end(&mut self, network: &Network, _optimizer: &mut dyn Optimizer, metrics: &Metrics) -> Control {
+        (self.0)(network, metrics)
+    }
+}
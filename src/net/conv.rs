@@ -0,0 +1,652 @@
+use std::fmt;
+
+use ndarray::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::activation_functions::Activation;
+use super::layer::{apply_dense, dense_params_mut, weightless_neuron, DenseGradients, Layer};
+use super::neuron::{Hidden, Neuron};
+use super::optimizers::Optimizer;
+use super::regularizers::Regularizer;
+
+// (channels, height, width) of one sample, the values between layers stay (batch_size, features) rows
+// and image layers read every row as such an array in row-major order, channels first
+pub type Shape3 = (usize, usize, usize);
+
+// (channels, length) of one sample of a sequence, read the same way
+pub type Shape2 = (usize, usize);
+
+// zeros added around the input of a convolution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Padding {
+    // none, the kernel only visits positions inside the input
+    Valid,
+    // enough to give ceil(input / stride) output positions along every axis, the odd zero goes after
+    Same,
+    // the given number of zeros before and after every axis
+    Zeros(usize),
+    // kernel - 1 zeros before and none after, so that no output sees the inputs after its own position
+    Causal,
+}
+
+impl Padding {
+    // (zeros before, zeros after) along an axis of length len, kernel counts the gaps of a dilated kernel
+    pub fn amounts(&self, len: usize, kernel: usize, stride: usize) -> (usize, usize) {
+        match *self {
+            Padding::Valid => (0, 0),
+            Padding::Same => {
+                let out = len.div_ceil(stride.max(1));
+                let total = ((out.max(1) - 1) * stride + kernel).saturating_sub(len);
+                (total / 2, total - total / 2)
+            }
+            Padding::Zeros(n) => (n, n),
+            Padding::Causal => (kernel.saturating_sub(1), 0),
+        }
+    }
+}
+
+impl fmt::Display for Padding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Padding::Valid => write!(f, "valid"),
+            Padding::Same => write!(f, "same"),
+            Padding::Zeros(n) => write!(f, "zeros({})", n),
+            Padding::Causal => write!(f, "causal"),
+        }
+    }
+}
+
+// output positions of a window of size kernel moved by stride over len values with the given padding,
+// 0 if the window does not fit or stride is 0
+pub(crate) fn output_len(len: usize, kernel: usize, stride: usize, (before, after): (usize, usize)) -> usize {
+    match (len + before + after).checked_sub(kernel) {
+        Some(room) if stride > 0 && kernel > 0 => room / stride + 1,
+        _ => 0,
+    }
+}
+
+// (batch_size * positions, filters) rows of one output position each into (batch_size, filters * positions)
+// rows, channels first
+fn to_channels_first(values: Array2<f32>, batch_size: usize) -> Array2<f32> {
+    let (rows, filters) = values.dim();
+    let positions = rows / batch_size;
+    let values = values
+        .into_shape((batch_size, positions, filters))
+        .expect("the rows hold whole samples")
+        .permuted_axes([0, 2, 1]);
+    values
+        .as_standard_layout()
+        .into_owned()
+        .into_shape((batch_size, filters * positions))
+        .expect("standard layout")
+}
+
+// the inverse of to_channels_first
+fn to_positions(values: &Array2<f32>, filters: usize) -> Array2<f32> {
+    let (batch_size, len) = values.dim();
+    let positions = len / filters;
+    let values = values
+        .view()
+        .into_shape((batch_size, filters, positions))
+        .expect("the rows hold whole filters")
+        .permuted_axes([0, 2, 1]);
+    values
+        .as_standard_layout()
+        .into_owned()
+        .into_shape((batch_size * positions, filters))
+        .expect("standard layout")
+}
+
+// gradients of the kernels for the (batch_size, filters * positions) deltas of a convolution that cut
+// the given patches, averaged over the samples
+fn kernel_gradients(patches: &Array2<f32>, deltas: &Array2<f32>, filters: usize, bias: bool) -> DenseGradients {
+    let batch_size = deltas.nrows() as f32;
+    let deltas = to_positions(deltas, filters);
+    let biases = match bias {
+        true => deltas.sum_axis(Axis(0)) / batch_size,
+        false => Array1::zeros(0),
+    };
+    DenseGradients {
+        weights: patches.t().dot(&deltas) / batch_size,
+        biases,
+    }
+}
+
+// a 2d convolution of (channels, height, width) inputs with filters kernels of (channels, kernel.0, kernel.1),
+// computed as a product of the input patches with the kernels, so the parameters and gradients are those
+// of a dense layer
+#[derive(Debug, Clone)]
+pub struct Conv2D {
+    // (channels * kernel height * kernel width, filters), column f holds the kernel of filter f
+    // in (channel, row, column) order
+    pub weights: Array2<f32>,
+    // one per filter
+    pub biases: Array1<f32>,
+    // (height, width) of the kernels and of the steps between them
+    pub kernel: (usize, usize),
+    pub stride: (usize, usize),
+    pub padding: Padding,
+    pub input_shape: Shape3,
+    // (filters, height, width)
+    pub output_shape: Shape3,
+    // (batch_size, filters * height * width) values of the last forward pass
+    pub input_values: Array2<f32>,
+    pub output_values: Array2<f32>,
+    pub activation: Activation,
+    pub bias: bool,
+    pub regularizer: Regularizer,
+    // (batch_size * output positions, channels * kernel height * kernel width) patches of the last forward pass
+    patches: Array2<f32>,
+}
+
+impl Conv2D {
+    // zero weights and biases, the builder draws the weights with the fan-in of a kernel
+    pub fn new(
+        input_shape: Shape3,
+        filters: usize,
+        kernel: (usize, usize),
+        stride: (usize, usize),
+        padding: Padding,
+        activation: Activation,
+    ) -> Self {
+        let (channels, height, width) = input_shape;
+        let output_shape = (
+            filters,
+            output_len(height, kernel.0, stride.0, padding.amounts(height, kernel.0, stride.0)),
+            output_len(width, kernel.1, stride.1, padding.amounts(width, kernel.1, stride.1)),
+        );
+        let layer_size = output_shape.0 * output_shape.1 * output_shape.2;
+        Self {
+            weights: Array2::zeros((channels * kernel.0 * kernel.1, filters)),
+            biases: Array1::zeros(filters),
+            kernel,
+            stride,
+            padding,
+            input_shape,
+            output_shape,
+            input_values: Array2::zeros((1, layer_size)),
+            output_values: Array2::zeros((1, layer_size)),
+            activation,
+            bias: true,
+            regularizer: Regularizer::default(),
+            patches: Array2::zeros((0, channels * kernel.0 * kernel.1)),
+        }
+    }
+
+    pub fn filters(&self) -> usize {
+        self.biases.len()
+    }
+
+    // the kernels as (filters, channels, kernel height, kernel width)
+    pub fn kernels(&self) -> Array4<f32> {
+        let (channels, (kh, kw)) = (self.input_shape.0, self.kernel);
+        self.weights
+            .t()
+            .as_standard_layout()
+            .into_owned()
+            .into_shape((self.filters(), channels, kh, kw))
+            .expect("one kernel per column")
+    }
+
+    // (top, left) zeros of the padded input
+    fn offsets(&self) -> (usize, usize) {
+        let (_, height, width) = self.input_shape;
+        (
+            self.padding.amounts(height, self.kernel.0, self.stride.0).0,
+            self.padding.amounts(width, self.kernel.1, self.stride.1).0,
+        )
+    }
+
+    // calls f(patch row, patch column, (sample, channel, row, column)) for every value of the input
+    // that lands in a patch, positions in the padding are skipped
+    fn for_each_patch_value(&self, batch_size: usize, mut f: impl FnMut(usize, usize, [usize; 4])) {
+        let (channels, height, width) = self.input_shape;
+        let (_, out_h, out_w) = self.output_shape;
+        let ((kh, kw), (sh, sw)) = (self.kernel, self.stride);
+        let (top, left) = self.offsets();
+        for sample in 0..batch_size {
+            for (oy, ox) in (0..out_h).flat_map(|oy| (0..out_w).map(move |ox| (oy, ox))) {
+                let row = (sample * out_h + oy) * out_w + ox;
+                for channel in 0..channels {
+                    for ky in 0..kh {
+                        let Some(y) = (oy * sh + ky).checked_sub(top).filter(|y| *y < height) else {
+                            continue;
+                        };
+                        for kx in 0..kw {
+                            let Some(x) = (ox * sw + kx).checked_sub(left).filter(|x| *x < width) else {
+                                continue;
+                            };
+                            f(row, (channel * kh + ky) * kw + kx, [sample, channel, y, x]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn forward(&mut self, inputs: &ArrayView2<f32>) {
+        let batch_size = inputs.nrows();
+        let (channels, height, width) = self.input_shape;
+        let images = inputs
+            .as_standard_layout()
+            .into_owned()
+            .into_shape((batch_size, channels, height, width))
+            .expect("one image per row");
+        let (_, out_h, out_w) = self.output_shape;
+        let mut patches = Array2::zeros((batch_size * out_h * out_w, self.weights.nrows()));
+        self.for_each_patch_value(batch_size, |row, column, index| patches[[row, column]] = images[index]);
+
+        let x = patches.dot(&self.weights) + &self.biases;
+        self.input_values = to_channels_first(x, batch_size);
+        self.output_values = self.activation.activate(&self.input_values);
+        self.patches = patches;
+    }
+
+    // gradients for the (batch_size, layer_size) deltas of the outputs, before the activation derivative,
+    // averaged over the samples and including those of the l1 and l2 penalties
+    pub fn gradients(&self, deltas: &Array2<f32>) -> DenseGradients {
+        let mut gradients = kernel_gradients(&self.patches, deltas, self.filters(), self.bias);
+        self.regularizer
+            .add_gradients(&self.weights, &self.biases, self.bias, &mut gradients);
+        gradients
+    }
+
+    // deltas propagated to the inputs, every patch adds its share back to the values it was cut from
+    pub fn propagate(&self, deltas: &Array2<f32>) -> Array2<f32> {
+        let batch_size = deltas.nrows();
+        let patches = to_positions(deltas, self.filters()).dot(&self.weights.t());
+        let (channels, height, width) = self.input_shape;
+        let mut propagated = Array4::zeros((batch_size, channels, height, width));
+        self.for_each_patch_value(batch_size, |row, column, index| propagated[index] += patches[[row, column]]);
+        propagated
+            .into_shape((batch_size, channels * height * width))
+            .expect("standard layout")
+    }
+
+    pub fn apply(&mut self, gradients: &DenseGradients, optimizer: &mut dyn Optimizer, layer: usize) {
+        apply_dense(
+            &mut self.weights,
+            &mut self.biases,
+            self.bias,
+            &self.regularizer,
+            gradients,
+            optimizer,
+            layer,
+        );
+    }
+
+    pub(crate) fn params_mut(&mut self) -> Vec<ArrayViewMutD<'_, f32>> {
+        dense_params_mut(&mut self.weights, &mut self.biases, self.bias)
+    }
+}
+
+impl Layer for Conv2D {
+    fn len_weights(&self) -> u32 {
+        self.weights.len() as u32
+    }
+
+    fn batch_values(&self) -> ArrayView2<'_, f32> {
+        self.output_values.view()
+    }
+
+    fn len(&self) -> usize {
+        let (filters, height, width) = self.output_shape;
+        filters * height * width
+    }
+
+    // the neurons of a filter share its kernel
+    fn get(&self, index: usize) -> Option<Neuron> {
+        if index >= self.len() {
+            return None;
+        }
+        let filter = index / (self.output_shape.1 * self.output_shape.2);
+        Some(Neuron::Hidden(Hidden {
+            input_value: self.input_values[[0, index]],
+            output_value: self.output_values[[0, index]],
+            weights: self.weights.column(filter).to_owned(),
+        }))
+    }
+
+    fn get_activation(&self) -> Activation {
+        self.activation
+    }
+}
+
+impl fmt::Display for Conv2D {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "conv2d: {:?} -> {:?}, kernel {:?}, stride {:?}, padding {}\nkernels: {}\nbiases: {}",
+            self.input_shape,
+            self.output_shape,
+            self.kernel,
+            self.stride,
+            self.padding,
+            self.kernels(),
+            self.biases
+        )
+    }
+}
+
+// a 1d convolution of (channels, length) sequences with filters kernels of (channels, kernel) whose taps
+// are dilation positions apart, computed like Conv2D
+#[derive(Debug, Clone)]
+pub struct Conv1D {
+    // (channels * kernel, filters), column f holds the kernel of filter f in (channel, tap) order
+    pub weights: Array2<f32>,
+    // one per filter
+    pub biases: Array1<f32>,
+    pub kernel: usize,
+    pub stride: usize,
+    pub dilation: usize,
+    pub padding: Padding,
+    pub input_shape: Shape2,
+    // (filters, length)
+    pub output_shape: Shape2,
+    // (batch_size, filters * length) values of the last forward pass
+    pub input_values: Array2<f32>,
+    pub output_values: Array2<f32>,
+    pub activation: Activation,
+    pub bias: bool,
+    pub regularizer: Regularizer,
+    // (batch_size * output positions, channels * kernel) patches of the last forward pass
+    patches: Array2<f32>,
+}
+
+impl Conv1D {
+    // zero weights and biases, the builder draws the weights with the fan-in of a kernel
+    pub fn new(
+        input_shape: Shape2,
+        filters: usize,
+        kernel: usize,
+        stride: usize,
+        dilation: usize,
+        padding: Padding,
+        activation: Activation,
+    ) -> Self {
+        let (channels, length) = input_shape;
+        let span = dilation * kernel.saturating_sub(1) + 1;
+        let output_shape = (filters, output_len(length, span, stride, padding.amounts(length, span, stride)));
+        let layer_size = output_shape.0 * output_shape.1;
+        Self {
+            weights: Array2::zeros((channels * kernel, filters)),
+            biases: Array1::zeros(filters),
+            kernel,
+            stride,
+            dilation,
+            padding,
+            input_shape,
+            output_shape,
+            input_values: Array2::zeros((1, layer_size)),
+            output_values: Array2::zeros((1, layer_size)),
+            activation,
+            bias: true,
+            regularizer: Regularizer::default(),
+            patches: Array2::zeros((0, channels * kernel)),
+        }
+    }
+
+    pub fn filters(&self) -> usize {
+        self.biases.len()
+    }
+
+    // the number of input positions a kernel reaches over, the taps and the gaps between them
+    pub fn span(&self) -> usize {
+        self.dilation * self.kernel.saturating_sub(1) + 1
+    }
+
+    // calls f(patch row, patch column, (sample, channel, position)) for every value of the input
+    // that lands in a patch, positions in the padding are skipped
+    fn for_each_patch_value(&self, batch_size: usize, mut f: impl FnMut(usize, usize, [usize; 3])) {
+        let (channels, length) = self.input_shape;
+        let out_len = self.output_shape.1;
+        let before = self.padding.amounts(length, self.span(), self.stride).0;
+        for sample in 0..batch_size {
+            for t in 0..out_len {
+                let row = sample * out_len + t;
+                for channel in 0..channels {
+                    for tap in 0..self.kernel {
+                        let position = t * self.stride + tap * self.dilation;
+                        let Some(position) = position.checked_sub(before).filter(|p| *p < length) else {
+                            continue;
+                        };
+                        f(row, channel * self.kernel + tap, [sample, channel, position]);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn forward(&mut self, inputs: &ArrayView2<f32>) {
+        let batch_size = inputs.nrows();
+        let (channels, length) = self.input_shape;
+        let sequences = inputs
+            .as_standard_layout()
+            .into_owned()
+            .into_shape((batch_size, channels, length))
+            .expect("one sequence per row");
+        let mut patches = Array2::zeros((batch_size * self.output_shape.1, self.weights.nrows()));
+        self.for_each_patch_value(batch_size, |row, column, index| patches[[row, column]] = sequences[index]);
+
+        let x = patches.dot(&self.weights) + &self.biases;
+        self.input_values = to_channels_first(x, batch_size);
+        self.output_values = self.activation.activate(&self.input_values);
+        self.patches = patches;
+    }
+
+    // gradients for the (batch_size, layer_size) deltas of the outputs, before the activation derivative,
+    // averaged over the samples and including those of the l1 and l2 penalties
+    pub fn gradients(&self, deltas: &Array2<f32>) -> DenseGradients {
+        let mut gradients = kernel_gradients(&self.patches, deltas, self.filters(), self.bias);
+        self.regularizer
+            .add_gradients(&self.weights, &self.biases, self.bias, &mut gradients);
+        gradients
+    }
+
+    // deltas propagated to the inputs, every patch adds its share back to the values it was cut from
+    pub fn propagate(&self, deltas: &Array2<f32>) -> Array2<f32> {
+        let batch_size = deltas.nrows();
+        let patches = to_positions(deltas, self.filters()).dot(&self.weights.t());
+        let (channels, length) = self.input_shape;
+        let mut propagated = Array3::zeros((batch_size, channels, length));
+        self.for_each_patch_value(batch_size, |row, column, index| propagated[index] += patches[[row, column]]);
+        propagated
+            .into_shape((batch_size, channels * length))
+            .expect("standard layout")
+    }
+
+    pub fn apply(&mut self, gradients: &DenseGradients, optimizer: &mut dyn Optimizer, layer: usize) {
+        apply_dense(
+            &mut self.weights,
+            &mut self.biases,
+            self.bias,
+            &self.regularizer,
+            gradients,
+            optimizer,
+            layer,
+        );
+    }
+
+    pub(crate) fn params_mut(&mut self) -> Vec<ArrayViewMutD<'_, f32>> {
+        dense_params_mut(&mut self.weights, &mut self.biases, self.bias)
+    }
+}
+
+impl Layer for Conv1D {
+    fn len_weights(&self) -> u32 {
+        self.weights.len() as u32
+    }
+
+    fn batch_values(&self) -> ArrayView2<'_, f32> {
+        self.output_values.view()
+    }
+
+    fn len(&self) -> usize {
+        self.output_shape.0 * self.output_shape.1
+    }
+
+    // the neurons of a filter share its kernel
+    fn get(&self, index: usize) -> Option<Neuron> {
+        if index >= self.len() {
+            return None;
+        }
+        Some(Neuron::Hidden(Hidden {
+            input_value: self.input_values[[0, index]],
+            output_value: self.output_values[[0, index]],
+            weights: self.weights.column(index / self.output_shape.1).to_owned(),
+        }))
+    }
+
+    fn get_activation(&self) -> Activation {
+        self.activation
+    }
+}
+
+impl fmt::Display for Conv1D {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "conv1d: {:?} -> {:?}, kernel {}, stride {}, dilation {}, padding {}\nweights: {}\nbiases: {}",
+            self.input_shape,
+            self.output_shape,
+            self.kernel,
+            self.stride,
+            self.dilation,
+            self.padding,
+            self.weights,
+            self.biases
+        )
+    }
+}
+
+// ends the image part of a network, the rows already are the flattened images so the values pass unchanged
+#[derive(Debug, Clone)]
+pub struct Flatten {
+    pub input_shape: Shape3,
+    // (batch_size, channels * height * width) values of the last forward pass
+    pub output_values: Array2<f32>,
+}
+
+impl Flatten {
+    pub fn new(input_shape: Shape3) -> Self {
+        let (channels, height, width) = input_shape;
+        Self {
+            input_shape,
+            output_values: Array2::zeros((1, channels * height * width)),
+        }
+    }
+
+    pub fn forward(&mut self, inputs: &ArrayView2<f32>) {
+        self.output_values = inputs.to_owned();
+    }
+
+    pub fn propagate(&self, deltas: &Array2<f32>) -> Array2<f32> {
+        deltas.clone()
+    }
+}
+
+impl Layer for Flatten {
+    fn len_weights(&self) -> u32 {
+        0
+    }
+
+    fn batch_values(&self) -> ArrayView2<'_, f32> {
+        self.output_values.view()
+    }
+
+    fn len(&self) -> usize {
+        let (channels, height, width) = self.input_shape;
+        channels * height * width
+    }
+
+    fn get(&self, index: usize) -> Option<Neuron> {
+        weightless_neuron(&self.output_values, &self.output_values, index)
+    }
+
+    fn get_activation(&self) -> Activation {
+        Activation::Identity
+    }
+}
+
+impl fmt::Display for Flatten {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "flatten: {:?}", self.input_shape)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn padding_test() {
+        assert_eq!((0, 0), Padding::Valid.amounts(5, 3, 1));
+        assert_eq!((1, 1), Padding::Same.amounts(5, 3, 1));
+        // the odd zero goes after
+        assert_eq!((0, 1), Padding::Same.amounts(4, 2, 1));
+        assert_eq!(3, output_len(5, 3, 2, Padding::Same.amounts(5, 3, 2)));
+        assert_eq!(4, output_len(3, 2, 1, Padding::Zeros(1).amounts(3, 2, 1)));
+        assert_eq!(0, output_len(2, 3, 1, (0, 0)));
+        assert_eq!(0, output_len(5, 3, 0, (0, 0)));
+    }
+
+    #[test]
+    fn conv2d_test() {
+        let inputs = array![[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0]];
+        let mut conv = Conv2D::new((1, 3, 3), 1, (2, 2), (1, 1), Padding::Valid, Activation::Identity);
+        conv.weights = array![[1.0], [0.0], [0.0], [1.0]];
+        conv.biases = array![0.5];
+        assert_eq!((1, 2, 2), conv.output_shape);
+        assert_eq!(Array4::from_shape_vec((1, 1, 2, 2), vec![1.0, 0.0, 0.0, 1.0]).unwrap(), conv.kernels());
+
+        conv.forward(&inputs.view());
+        assert_eq!(array![[6.5, 8.5, 12.5, 14.5]], conv.output_values);
+        // every input gets the weights of the kernel taps that read it
+        assert_eq!(
+            array![[1.0, 1.0, 0.0, 1.0, 2.0, 1.0, 0.0, 1.0, 1.0]],
+            conv.propagate(&Array2::ones((1, 4)))
+        );
+        let gradients = conv.gradients(&Array2::ones((1, 4)));
+        assert_eq!(array![[12.0], [16.0], [24.0], [28.0]], gradients.weights);
+        assert_eq!(array![4.0], gradients.biases);
+
+        let mut same = Conv2D::new((1, 3, 3), 1, (2, 2), (1, 1), Padding::Same, Activation::Identity);
+        same.weights = conv.weights.clone();
+        same.forward(&inputs.view());
+        assert_eq!(array![[6.0, 8.0, 3.0, 12.0, 14.0, 6.0, 7.0, 8.0, 9.0]], same.output_values);
+
+        // the outputs of every filter stay together
+        let mut strided = Conv2D::new((1, 3, 3), 2, (2, 2), (2, 2), Padding::Valid, Activation::Identity);
+        strided.weights = array![[1.0, 0.0], [0.0, 0.0], [0.0, 0.0], [1.0, -1.0]];
+        strided.forward(&array![[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0], [0.0; 9]].view());
+        assert_eq!(array![[6.0, -5.0], [0.0, 0.0]], strided.output_values);
+    }
+
+    #[test]
+    fn conv1d_test() {
+        let inputs = array![[1.0, 2.0, 3.0, 4.0, 5.0]];
+        let mut causal = Conv1D::new((1, 5), 1, 2, 1, 2, Padding::Causal, Activation::Identity);
+        causal.weights = array![[1.0], [10.0]];
+        assert_eq!(3, causal.span());
+        assert_eq!((1, 5), causal.output_shape);
+
+        // every output sees its own position and the one two before
+        causal.forward(&inputs.view());
+        assert_eq!(array![[10.0, 20.0, 31.0, 42.0, 53.0]], causal.output_values);
+        causal.forward(&array![[1.0, 2.0, 3.0, 4.0, 0.0]].view());
+        assert_eq!(array![[10.0, 20.0, 31.0, 42.0, 3.0]], causal.output_values);
+
+        let mut valid = Conv1D::new((1, 5), 1, 2, 1, 2, Padding::Valid, Activation::Identity);
+        valid.weights = causal.weights.clone();
+        valid.forward(&inputs.view());
+        assert_eq!(array![[31.0, 42.0, 53.0]], valid.output_values);
+        assert_eq!(array![[1.0, 1.0, 11.0, 10.0, 10.0]], valid.propagate(&Array2::ones((1, 3))));
+
+        // two channels into two filters of stride 2
+        let mut strided = Conv1D::new((2, 4), 2, 1, 2, 1, Padding::Valid, Activation::Identity);
+        strided.weights = array![[1.0, 0.0], [0.0, 1.0]];
+        strided.forward(&array![[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0]].view());
+        assert_eq!(array![[1.0, 3.0, 5.0, 7.0]], strided.output_values);
+    }
+}
@@ -0,0 +1,384 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use ndarray::{Array1, Array2, Axis};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use super::error::Error;
+
+// a column of a data file, by position counted from 0 or by its header name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    Index(usize),
+    Name(String),
+}
+
+impl From<usize> for Column {
+    fn from(index: usize) -> Self {
+        Column::Index(index)
+    }
+}
+
+impl From<&str> for Column {
+    fn from(name: &str) -> Self {
+        Column::Name(name.to_string())
+    }
+}
+
+// how to read a csv file, the label columns become the targets and every other column a feature
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvOptions {
+    pub delimiter: char,
+    pub has_header: bool,
+    pub label_columns: Vec<Column>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            has_header: true,
+            label_columns: vec![Column::Index(0)],
+        }
+    }
+}
+
+impl CsvOptions {
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn has_header(mut self, has_header: bool) -> Self {
+        self.has_header = has_header;
+        self
+    }
+
+    pub fn label_columns<C: Into<Column>>(mut self, columns: impl IntoIterator<Item = C>) -> Self {
+        self.label_columns = columns.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+// splits a line at the delimiter, fields may be quoted to contain the delimiter, "" is an escaped quote
+fn split_fields(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields.iter().map(|f| f.trim().to_string()).collect()
+}
+
+// one sample per row, features feed the input layer and targets are the expected outputs
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dataset {
+    pub features: Array2<f32>,
+    pub targets: Array2<f32>,
+}
+
+impl Dataset {
+    pub fn new(features: Array2<f32>, targets: Array2<f32>) -> Result<Self, Error> {
+        if features.nrows() != targets.nrows() {
+            return Err(Error::RowMismatch {
+                features: features.nrows(),
+                targets: targets.nrows(),
+            });
+        }
+        Ok(Self { features, targets })
+    }
+
+    // number of samples
+    pub fn len(&self) -> usize {
+        self.features.nrows()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // features and targets of one sample, e.g. for InputLayer::set_inputs(features.to_vec())
+    pub fn sample(&self, index: usize) -> Option<(Array1<f32>, Array1<f32>)> {
+        if index >= self.len() {
+            return None;
+        }
+        Some((self.features.row(index).to_owned(), self.targets.row(index).to_owned()))
+    }
+
+    // the samples at the given row indices, in that order
+    pub fn select(&self, indices: &[usize]) -> Dataset {
+        Dataset {
+            features: self.features.select(Axis(0), indices),
+            targets: self.targets.select(Axis(0), indices),
+        }
+    }
+
+    pub fn from_csv<P: AsRef<Path>>(path: P, options: &CsvOptions) -> Result<Self, Error> {
+        Self::from_csv_reader(File::open(path)?, options)
+    }
+
+    // every non empty line is a sample, all fields have to be numbers
+    pub fn from_csv_reader<R: Read>(reader: R, options: &CsvOptions) -> Result<Self, Error> {
+        let mut lines = BufReader::new(reader)
+            .lines()
+            .enumerate()
+            .map(|(n, line)| line.map(|line| (n + 1, line)))
+            .filter(|line| !matches!(line, Ok((_, line)) if line.trim().is_empty()));
+
+        let header = match options.has_header {
+            true => lines.next().transpose()?.map(|(_, line)| split_fields(&line, options.delimiter)),
+            false => None,
+        };
+
+        let mut rows: Vec<(usize, Vec<String>)> = vec![];
+        for line in lines {
+            let (n, line) = line?;
+            rows.push((n, split_fields(&line, options.delimiter)));
+        }
+        let columns = match (&header, rows.first()) {
+            (Some(header), _) => header.len(),
+            (None, Some((_, fields))) => fields.len(),
+            (None, None) => 0,
+        };
+
+        let mut labels = vec![];
+        for column in &options.label_columns {
+            let index = match column {
+                Column::Index(index) => Some(*index).filter(|i| *i < columns),
+                Column::Name(name) => header.as_ref().and_then(|h| h.iter().position(|f| f == name)),
+            };
+            let index = index.ok_or_else(|| Error::MissingColumn(format!("{:?}", column)))?;
+            labels.push(index);
+        }
+        let features: Vec<usize> = (0..columns).filter(|c| !labels.contains(c)).collect();
+
+        let mut feature_values = Vec::with_capacity(rows.len() * features.len());
+        let mut target_values = Vec::with_capacity(rows.len() * labels.len());
+        for (n, fields) in &rows {
+            if fields.len() != columns {
+                return Err(Error::ColumnCount {
+                    line: *n,
+                    expected: columns,
+                    found: fields.len(),
+                });
+            }
+            let parse = |c: usize| {
+                fields[c].parse::<f32>().map_err(|_| Error::Parse {
+                    line: *n,
+                    column: c + 1,
+                    value: fields[c].clone(),
+                })
+            };
+            for &c in &features {
+                feature_values.push(parse(c)?);
+            }
+            for &c in &labels {
+                target_values.push(parse(c)?);
+            }
+        }
+
+        let features = Array2::from_shape_vec((rows.len(), features.len()), feature_values).unwrap();
+        let targets = Array2::from_shape_vec((rows.len(), labels.len()), target_values).unwrap();
+        Self::new(features, targets)
+    }
+
+    // the class of every sample, the index of the largest target for one-hot rows, else the target itself,
+    // which must then be a whole number from 0, so regression targets are not mistaken for classes
+    pub fn classes(&self) -> Result<Vec<u32>, Error> {
+        self.targets
+            .axis_iter(Axis(0))
+            .enumerate()
+            .map(|(n, row)| match row.len() {
+                1 if row[0] >= 0.0 && row[0] <= u32::MAX as f32 && row[0].fract() == 0.0 => Ok(row[0] as u32),
+                1 => Err(Error::InvalidLabel { row: n, value: row[0] }),
+                _ => Ok(row
+                    .iter()
+                    .enumerate()
+                    .fold((0, f32::NEG_INFINITY), |(i, m), (j, v)| if *v > m { (j, *v) } else { (i, m) })
+                    .0 as u32),
+            })
+            .collect()
+    }
+
+    // split off the last fraction of the rows, returns (first part, last part)
+    pub fn split(&self, fraction: f32) -> (Dataset, Dataset) {
+        let tail = ((self.len() as f32) * fraction.clamp(0.0, 1.0)).round() as usize;
+        let head: Vec<usize> = (0..self.len() - tail).collect();
+        let rest: Vec<usize> = (self.len() - tail..self.len()).collect();
+        (self.select(&head), self.select(&rest))
+    }
+
+    // shuffles every class with the seed and deals validation and test fractions of it out,
+    // so all three parts keep the class proportions, returns (train, validation, test) in row order,
+    // the counts are rounded per class so a class smaller than about 1 / fraction has no rows in that part
+    pub fn stratified_split(&self, validation: f32, test: f32, seed: u64) -> Result<(Dataset, Dataset, Dataset), Error> {
+        let valid = |f: f32| (0.0..=1.0).contains(&f);
+        if !valid(validation) || !valid(test) || validation + test >= 1.0 {
+            return Err(Error::InvalidSplit { validation, test });
+        }
+
+        // BTreeMap so classes are visited in the same order on every run
+        let mut by_class: BTreeMap<u32, Vec<usize>> = BTreeMap::new();
+        for (row, class) in self.classes()?.into_iter().enumerate() {
+            by_class.entry(class).or_default().push(row);
+        }
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (mut train_rows, mut validation_rows, mut test_rows) = (vec![], vec![], vec![]);
+        for rows in by_class.values_mut() {
+            rows.shuffle(&mut rng);
+            let n_validation = (rows.len() as f32 * validation).round() as usize;
+            let n_test = ((rows.len() as f32 * test).round() as usize).min(rows.len() - n_validation);
+            validation_rows.extend_from_slice(&rows[..n_validation]);
+            test_rows.extend_from_slice(&rows[n_validation..n_validation + n_test]);
+            train_rows.extend_from_slice(&rows[n_validation + n_test..]);
+        }
+
+        // too few rows for the fractions, e.g. only classes smaller than about 1 / fraction
+        if (validation > 0.0 && validation_rows.is_empty()) || (test > 0.0 && test_rows.is_empty()) {
+            return Err(Error::InvalidSplit { validation, test });
+        }
+
+        for rows in [&mut train_rows, &mut validation_rows, &mut test_rows] {
+            rows.sort_unstable();
+        }
+        Ok((self.select(&train_rows), self.select(&validation_rows), self.select(&test_rows)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+
+    const CSV: &str = "label,x,\"y, scaled\"
+1,0.5,-1
+
+0,1.5,2e-1
+";
+
+    #[test]
+    fn csv_test() {
+        let dataset = Dataset::from_csv_reader(CSV.as_bytes(), &CsvOptions::default()).unwrap();
+        assert_eq!(array![[0.5, -1.0], [1.5, 0.2]], dataset.features);
+        assert_eq!(array![[1.0], [0.0]], dataset.targets);
+
+        let options = CsvOptions::default().label_columns(["y, scaled", "x"]);
+        let dataset = Dataset::from_csv_reader(CSV.as_bytes(), &options).unwrap();
+        assert_eq!(array![[1.0], [0.0]], dataset.features);
+        assert_eq!(array![[-1.0, 0.5], [0.2, 1.5]], dataset.targets);
+
+        let options = CsvOptions::default().has_header(false).delimiter(';').label_columns([2]);
+        let dataset = Dataset::from_csv_reader("1;2;3\n4;5;6".as_bytes(), &options).unwrap();
+        assert_eq!(array![[1.0, 2.0], [4.0, 5.0]], dataset.features);
+        assert_eq!(array![[3.0], [6.0]], dataset.targets);
+    }
+
+    #[test]
+    fn csv_error_test() {
+        let options = CsvOptions::default().has_header(false);
+        assert_eq!(
+            Err(Error::Parse {
+                line: 2,
+                column: 2,
+                value: "abc".to_string()
+            }),
+            Dataset::from_csv_reader("1,2\n3,abc".as_bytes(), &options)
+        );
+        assert_eq!(
+            Err(Error::ColumnCount {
+                line: 2,
+                expected: 2,
+                found: 3
+            }),
+            Dataset::from_csv_reader("1,2\n3,4,5".as_bytes(), &options)
+        );
+        assert_eq!(
+            Err(Error::MissingColumn("Name(\"z\")".to_string())),
+            Dataset::from_csv_reader(CSV.as_bytes(), &CsvOptions::default().label_columns(["z"]))
+        );
+        assert!(matches!(
+            Dataset::from_csv("does/not/exist.csv", &CsvOptions::default()),
+            Err(Error::Io(_))
+        ));
+    }
+
+    #[test]
+    fn stratified_split_test() {
+        // 80 samples of class 0 and 20 of class 1, one-hot encoded
+        let features = Array2::from_shape_fn((100, 1), |(i, _)| i as f32);
+        let targets = Array2::from_shape_fn((100, 2), |(i, j)| ((i % 5 == 0) == (j == 1)) as u8 as f32);
+        let dataset = Dataset::new(features, targets).unwrap();
+
+        let (train, validation, test) = dataset.stratified_split(0.2, 0.1, 7).unwrap();
+        assert_eq!((70, 20, 10), (train.len(), validation.len(), test.len()));
+        assert_eq!(4, validation.classes().unwrap().iter().filter(|c| **c == 1).count());
+        assert_eq!(2, test.classes().unwrap().iter().filter(|c| **c == 1).count());
+
+        // every sample ends up in exactly one part
+        let mut rows: Vec<f32> = [&train, &validation, &test]
+            .iter()
+            .flat_map(|d| d.features.iter().copied().collect::<Vec<_>>())
+            .collect();
+        rows.sort_by(f32::total_cmp);
+        assert_eq!(dataset.features.iter().copied().collect::<Vec<_>>(), rows);
+
+        // the same seed gives the same split
+        assert_eq!((train, validation, test), dataset.stratified_split(0.2, 0.1, 7).unwrap());
+        assert_eq!(
+            Err(Error::InvalidSplit {
+                validation: 0.6,
+                test: 0.4
+            }),
+            dataset.stratified_split(0.6, 0.4, 7)
+        );
+    }
+
+    #[test]
+    fn classes_test() {
+        // -0.0 and 0.0 are the same class
+        let dataset = Dataset::new(Array2::zeros((4, 1)), array![[0.0], [-0.0], [2.0], [0.0]]).unwrap();
+        assert_eq!(vec![0, 0, 2, 0], dataset.classes().unwrap());
+        let (train, validation, test) = dataset.stratified_split(0.25, 0.0, 1).unwrap();
+        assert_eq!((3, 1, 0), (train.len(), validation.len(), test.len()));
+        assert_eq!(vec![0], validation.classes().unwrap());
+
+        // regression targets are not classes
+        let dataset = Dataset::new(Array2::zeros((4, 1)), array![[0.0], [0.5], [1.0], [-1.0]]).unwrap();
+        assert_eq!(Err(Error::InvalidLabel { row: 1, value: 0.5 }), dataset.classes());
+        assert_eq!(
+            Err(Error::InvalidLabel { row: 1, value: 0.5 }),
+            dataset.stratified_split(0.25, 0.25, 1)
+        );
+        let dataset = Dataset::new(Array2::zeros((2, 1)), array![[1.0], [-1.0]]).unwrap();
+        assert_eq!(Err(Error::InvalidLabel { row: 1, value: -1.0 }), dataset.classes());
+
+        // classes of one row each get no validation or test rows
+        let dataset = Dataset::new(Array2::zeros((4, 1)), array![[0.0], [1.0], [2.0], [3.0]]).unwrap();
+        assert_eq!(
+            Err(Error::InvalidSplit {
+                validation: 0.25,
+                test: 0.0
+            }),
+            dataset.stratified_split(0.25, 0.0, 1)
+        );
+        let (train, validation, test) = dataset.stratified_split(0.0, 0.0, 1).unwrap();
+        assert_eq!((4, 0, 0), (train.len(), validation.len(), test.len()));
+    }
+}
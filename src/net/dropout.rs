@@ -0,0 +1,132 @@
+use std::fmt;
+
+use ndarray::prelude::*;
+use rand::Rng;
+
+use super::activation_functions::Activation;
+use super::layer::Layer;
+use super::neuron::{Hidden, Neuron};
+
+// inverted dropout: in training mode every value is zeroed with probability rate and the others
+// are scaled by 1 / (1 - rate), so that inference passes the values through unchanged
+#[derive(Debug, Clone)]
+pub struct Dropout {
+    pub rate: f32,
+    // (batch_size, layer_size) values of the last forward pass
+    pub input_values: Array2<f32>,
+    pub output_values: Array2<f32>,
+    // the scaled mask of the last forward pass in training mode, None after an inference pass
+    pub mask: Option<Array2<f32>>,
+}
+
+impl Dropout {
+    // keeps the size of prev_layer, rate must be in [0, 1)
+    pub fn new(rate: f32, prev_layer: &dyn Layer) -> Self {
+        let layer_size = prev_layer.len();
+        Self {
+            rate,
+            input_values: Array2::zeros((1, layer_size)),
+            output_values: Array2::zeros((1, layer_size)),
+            mask: None,
+        }
+    }
+
+    // draws a new mask from rng in training mode, the mask is kept for the backward pass
+    pub fn forward<R: Rng + ?Sized>(&mut self, inputs: &ArrayView2<f32>, training: bool, rng: &mut R) {
+        self.input_values = inputs.to_owned();
+        self.mask = match training && self.rate > 0.0 {
+            true => {
+                let (rate, scale) = (self.rate, 1.0 / (1.0 - self.rate));
+                Some(Array2::from_shape_simple_fn(inputs.raw_dim(), || match rng.gen::<f32>() < rate {
+                    true => 0.0,
+                    false => scale,
+                }))
+            }
+            false => None,
+        };
+        self.output_values = match &self.mask {
+            Some(mask) => inputs * mask,
+            None => inputs.to_owned(),
+        };
+    }
+
+    // deltas of the outputs propagated to the inputs, dropped values get none
+    pub fn propagate(&self, deltas: &Array2<f32>) -> Array2<f32> {
+        match &self.mask {
+            Some(mask) => deltas * mask,
+            None => deltas.clone(),
+        }
+    }
+}
+
+impl Layer for Dropout {
+    fn len_weights(&self) -> u32 {
+        0
+    }
+
+    fn batch_values(&self) -> ArrayView2<'_, f32> {
+        self.output_values.view()
+    }
+
+    fn len(&self) -> usize {
+        self.output_values.ncols()
+    }
+
+    fn get(&self, index: usize) -> Option<Neuron> {
+        if index >= self.len() {
+            return None;
+        }
+        Some(Neuron::Hidden(Hidden {
+            input_value: self.input_values[[0, index]],
+            output_value: self.output_values[[0, index]],
+            weights: Array1::zeros(0),
+        }))
+    }
+
+    fn get_activation(&self) -> Activation {
+        Activation::Identity
+    }
+}
+
+impl fmt::Display for Dropout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "dropout: {}", self.rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::net::layer::InputLayer;
+
+    use super::*;
+
+    #[test]
+    fn dropout_test() {
+        let mut dropout = Dropout::new(0.25, &InputLayer::new(1000));
+        assert_eq!(1000, dropout.len());
+        let inputs = Array2::from_elem((4, 1000), 2.0);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        dropout.forward(&inputs.view(), true, &mut rng);
+        let outputs = &dropout.output_values;
+        assert!(outputs.iter().all(|v| *v == 0.0 || (v - 2.0 / 0.75).abs() < 1e-6));
+        let dropped = outputs.iter().filter(|v| **v == 0.0).count() as f32 / outputs.len() as f32;
+        assert!((dropped - 0.25).abs() < 0.02, "{}", dropped);
+        // the expected value is unchanged
+        assert!((outputs.mean().unwrap() - 2.0).abs() < 0.05);
+
+        // deltas only flow back through the kept values
+        let deltas = dropout.propagate(&Array2::ones((4, 1000)));
+        assert_eq!(dropout.mask.as_ref().unwrap(), &deltas);
+        assert_eq!(&(&inputs * &deltas), outputs);
+
+        // inference passes everything through
+        dropout.forward(&inputs.view(), false, &mut rng);
+        assert_eq!(inputs, dropout.output_values);
+        assert!(dropout.mask.is_none());
+        assert_eq!(inputs, dropout.propagate(&inputs));
+    }
+}
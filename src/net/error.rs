@@ -0,0 +1,87 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    // a layer received a different number of values than it expects
+    ShapeMismatch {
+        layer: usize,
+        expected: usize,
+        found: usize,
+    },
+    // a network needs at least one layer after the input layer
+    EmptyNetwork,
+    // every layer needs at least one neuron
+    EmptyLayer { layer: usize },
+    // a batch needs at least one row
+    EmptyBatch,
+    // a layer is configured in a way it cannot work, e.g. a dropout rate of 1
+    InvalidLayer { layer: usize, message: String },
+    // a layer received or produced a NaN or infinite value
+    NonFinite { layer: usize },
+    // features and targets of a dataset must have the same number of rows
+    RowMismatch { features: usize, targets: usize },
+    // reading a dataset from disk failed
+    Io(String),
+    // a field of a data file could not be parsed, line and column count from 1
+    Parse { line: usize, column: usize, value: String },
+    // a data file is not in the expected format
+    Format(String),
+    // a line of a data file has a different number of fields than the first one
+    ColumnCount { line: usize, expected: usize, found: usize },
+    // a column was selected that the data file does not have
+    MissingColumn(String),
+    // split fractions must be in [0, 1], leave rows for training and give every part with a
+    // non-zero fraction at least one row
+    InvalidSplit { validation: f32, test: f32 },
+    // a single target column must hold class ids, whole numbers from 0, row counts from 0
+    InvalidLabel { row: usize, value: f32 },
+    // a neuron view was converted into the wrong kind of neuron
+    WrongNeuronKind {
+        expected: &'static str,
+        found: &'static str,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::ShapeMismatch {
+                layer,
+                expected,
+                found,
+            } => write!(f, "layer {}: expected {} values, found {}", layer, expected, found),
+            Error::EmptyNetwork => write!(f, "network has no layers"),
+            Error::EmptyLayer { layer } => write!(f, "layer {} has no neurons", layer),
+            Error::EmptyBatch => write!(f, "batch has no rows"),
+            Error::InvalidLayer { layer, message } => write!(f, "layer {}: {}", layer, message),
+            Error::NonFinite { layer } => write!(f, "layer {}: non-finite value", layer),
+            Error::RowMismatch { features, targets } => {
+                write!(f, "{} feature rows but {} target rows", features, targets)
+            }
+            Error::Io(message) => write!(f, "io error: {}", message),
+            Error::Parse { line, column, value } => {
+                write!(f, "line {}, column {}: cannot parse {:?}", line, column, value)
+            }
+            Error::Format(message) => write!(f, "format error: {}", message),
+            Error::ColumnCount { line, expected, found } => {
+                write!(f, "line {}: expected {} fields, found {}", line, expected, found)
+            }
+            Error::MissingColumn(column) => write!(f, "no column {}", column),
+            Error::InvalidSplit { validation, test } => {
+                write!(f, "invalid split: validation {}, test {}", validation, test)
+            }
+            Error::InvalidLabel { row, value } => write!(f, "row {}: {} is not a class id", row, value),
+            Error::WrongNeuronKind { expected, found } => {
+                write!(f, "expected {} neuron, found {} neuron", expected, found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error.to_string())
+    }
+}
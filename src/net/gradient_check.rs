@@ -0,0 +1,241 @@
+use ndarray::{Array2, Dimension};
+use rand_chacha::ChaCha8Rng;
+
+use super::error::Error;
+use super::network::Network;
+
+// keeps the relative error finite where both gradients are close to zero,
+// f32 finite differences cannot resolve gradients much smaller than this anyway
+const FLOOR: f32 = 1e-4;
+
+fn relative_error(analytic: f32, numeric: f32) -> f32 {
+    (analytic - numeric).abs() / (analytic.abs() + numeric.abs()).max(FLOOR)
+}
+
+// every forward pass restarts from rng, so the dropout masks match those of compute_gradients
+fn loss(network: &mut Network, rng: &ChaCha8Rng, input: &Array2<f32>, target: &Array2<f32>) -> Result<f32, Error> {
+    network.set_rng(rng.clone());
+    network.forward_batch(input)?;
+    network.calc_batch_error(target)
+}
+
+// compares the backpropagated gradients of the loss on (input, target) against central differences
+// (loss(w + eps) - loss(w - eps)) / 2eps for every trainable parameter, the network is left untouched,
+// returns the largest relative error of every layer after the input layer, output layer last,
+// layers without parameters report 0
+pub fn gradient_check(network: &Network, input: &Array2<f32>, target: &Array2<f32>, eps: f32) -> Result<Vec<f32>, Error> {
+    let analytic = network.compute_gradients(input, target)?.layers;
+    let mut probe = network.clone();
+    let rng = network.rng_state();
+
+    let mut errors = vec![0.0f32; analytic.len()];
+    for (n, gradients) in analytic.iter().enumerate() {
+        for (array, gradients) in gradients.arrays().iter().enumerate() {
+            for (index, analytic) in gradients.indexed_iter() {
+                let param = Param {
+                    layer: n,
+                    array,
+                    index: index.slice().to_vec(),
+                };
+                let numeric = central_difference(&mut probe, rng, input, target, eps, &param)?;
+                errors[n] = errors[n].max(relative_error(*analytic, numeric));
+            }
+        }
+    }
+    Ok(errors)
+}
+
+// a single value of a parameter array of a layer
+struct Param {
+    layer: usize,
+    array: usize,
+    index: Vec<usize>,
+}
+
+fn shift(network: &mut Network, param: &Param, delta: f32) {
+    network.params_mut()[param.layer][param.array][param.index.as_slice()] += delta;
+}
+
+fn central_difference(
+    network: &mut Network,
+    rng: &ChaCha8Rng,
+    input: &Array2<f32>,
+    target: &Array2<f32>,
+    eps: f32,
+    param: &Param,
+) -> Result<f32, Error> {
+    shift(network, param, eps);
+    let plus = loss(network, rng, input, target)?;
+    shift(network, param, -2.0 * eps);
+    let minus = loss(network, rng, input, target)?;
+    shift(network, param, eps);
+    Ok((plus - minus) / (2.0 * eps))
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{array, s};
+
+    use crate::net::activation_functions::Activation;
+    use crate::net::builder::NetworkBuilder;
+    use crate::net::conv::Padding;
+    use crate::net::loss_functions::BinaryCrossEntropy;
+    use crate::net::regularizers::Regularizer;
+
+    use super::*;
+
+    #[test]
+    fn gradient_check_test() {
+        let input = array![[0.5, -1.0, 0.25], [-0.3, 0.8, 1.2]];
+
+        let mut network = NetworkBuilder::new(3)
+            .seed(1)
+            .dense(4, Activation::Tanh)
+            .dense(3, Activation::Softmax)
+            .build()
+            .unwrap();
+        let before = network.clone().forward_batch(&input).unwrap();
+        let errors = gradient_check(&network, &input, &array![[1.0, 0.0, 0.0], [0.0, 0.0, 1.0]], 1e-2).unwrap();
+        assert_eq!(2, errors.len());
+        assert!(errors.iter().all(|e| *e < 1e-2), "{:?}", errors);
+        // the network is left untouched
+        assert_eq!(before, network.forward_batch(&input).unwrap());
+
+        let mut network = NetworkBuilder::new(3)
+            .seed(2)
+            .bias(false)
+            .dense(4, Activation::Sigmoid)
+            .bias(true)
+            .dense(2, Activation::Sigmoid)
+            .build()
+            .unwrap();
+        network.set_loss(BinaryCrossEntropy);
+        let errors = gradient_check(&network, &input, &array![[1.0, 0.0], [0.0, 1.0]], 1e-2).unwrap();
+        assert!(errors.iter().all(|e| *e < 1e-2), "{:?}", errors);
+
+        // the penalties are part of the loss and of the gradients
+        let network = NetworkBuilder::new(3)
+            .seed(3)
+            .regularizer(Regularizer::default().l1(0.01).l2(0.05).include_bias(true))
+            .dense(4, Activation::Tanh)
+            .dense(1, Activation::Identity)
+            .build()
+            .unwrap();
+        let errors = gradient_check(&network, &input, &array![[1.0], [-1.0]], 1e-2).unwrap();
+        assert!(errors.iter().all(|e| *e < 1e-2), "{:?}", errors);
+
+        // dropout uses the same masks in both passes
+        let network = NetworkBuilder::new(3)
+            .seed(4)
+            .dense(8, Activation::Tanh)
+            .dropout(0.5)
+            .dense(2, Activation::Softmax)
+            .build()
+            .unwrap();
+        let errors = gradient_check(&network, &input, &array![[1.0, 0.0], [0.0, 1.0]], 1e-2).unwrap();
+        assert_eq!(3, errors.len());
+        assert!(errors.iter().all(|e| *e < 1e-2), "{:?}", errors);
+
+        // batch normalisation couples the samples of the batch
+        let input = array![[0.5, -1.0, 0.25], [-0.3, 0.8, 1.2], [1.5, 0.1, -0.7], [0.0, -0.4, 0.9]];
+        let target = array![[1.0, 0.0], [0.0, 1.0], [0.0, 1.0], [1.0, 0.0]];
+        let network = NetworkBuilder::new(3)
+            .seed(5)
+            .bias(false)
+            .dense(5, Activation::Identity)
+            .batch_norm()
+            .activation(Activation::Sigmoid)
+            .bias(true)
+            .dense(2, Activation::Softmax)
+            .build()
+            .unwrap();
+        let errors = gradient_check(&network, &input, &target, 1e-2).unwrap();
+        assert_eq!(4, errors.len());
+        assert!(errors.iter().all(|e| *e < 1e-2), "{:?}", errors);
+
+        // the per sample normalisations also work on a single sample
+        let network = NetworkBuilder::new(3)
+            .seed(6)
+            .dense(6, Activation::Identity)
+            .layer_norm()
+            .activation(Activation::Tanh)
+            .dense(4, Activation::Identity)
+            .rms_norm()
+            .dense(2, Activation::Softmax)
+            .build()
+            .unwrap();
+        let errors = gradient_check(&network, &input, &target, 1e-2).unwrap();
+        assert_eq!(6, errors.len());
+        assert!(errors.iter().all(|e| *e < 1e-2), "{:?}", errors);
+        let (input, target) = (input.slice(s![..1, ..]).to_owned(), target.slice(s![..1, ..]).to_owned());
+        let errors = gradient_check(&network, &input, &target, 1e-2).unwrap();
+        assert!(errors.iter().all(|e| *e < 1e-2), "{:?}", errors);
+    }
+
+    #[test]
+    fn conv_gradient_check_test() {
+        // two samples of (2, 4, 4) images
+        let input = Array2::from_shape_fn((2, 32), |(i, j)| ((i * 32 + j) as f32 * 0.37).sin());
+        let target = array![[1.0, 0.0], [0.0, 1.0]];
+
+        let network = NetworkBuilder::new(32)
+            .seed(7)
+            .reshape(2, 4, 4)
+            .conv2d(3, (3, 3), (1, 1), Padding::Same, Activation::Tanh)
+            .max_pool2d((2, 2), (2, 2))
+            .flatten()
+            .dense(2, Activation::Softmax)
+            .build()
+            .unwrap();
+        // small enough steps that no maximum of the pooling changes place
+        let errors = gradient_check(&network, &input, &target, 3e-3).unwrap();
+        assert_eq!(4, errors.len());
+        assert!(errors.iter().all(|e| *e < 1e-2), "{:?}", errors);
+
+        let network = NetworkBuilder::new(32)
+            .seed(9)
+            .reshape(2, 4, 4)
+            .conv2d(2, (2, 3), (2, 1), Padding::Zeros(1), Activation::Sigmoid)
+            .avg_pool2d((2, 2), (1, 1))
+            .bias(false)
+            .conv2d(2, (1, 2), (1, 1), Padding::Valid, Activation::Identity)
+            .bias(true)
+            .dense(2, Activation::Softmax)
+            .build()
+            .unwrap();
+        let errors = gradient_check(&network, &input, &target, 1e-2).unwrap();
+        assert!(errors.iter().all(|e| *e < 1e-2), "{:?}", errors);
+    }
+
+    #[test]
+    fn conv1d_gradient_check_test() {
+        // two samples of (2, 8) sequences
+        let input = Array2::from_shape_fn((2, 16), |(i, j)| ((i * 16 + j) as f32 * 0.53).cos());
+        let target = array![[1.0, 0.0], [0.0, 1.0]];
+
+        let network = NetworkBuilder::new(16)
+            .seed(10)
+            .reshape1d(2, 8)
+            .conv1d(3, 3, 1, 2, Padding::Causal, Activation::Tanh)
+            .conv1d(2, 2, 2, 1, Padding::Same, Activation::Identity)
+            .global_average_pool1d()
+            .dense(2, Activation::Softmax)
+            .build()
+            .unwrap();
+        let errors = gradient_check(&network, &input, &target, 1e-2).unwrap();
+        assert_eq!(4, errors.len());
+        assert!(errors.iter().all(|e| *e < 1e-2), "{:?}", errors);
+
+        let network = NetworkBuilder::new(16)
+            .seed(11)
+            .reshape1d(2, 8)
+            .conv1d(3, 2, 1, 3, Padding::Zeros(1), Activation::Sigmoid)
+            .global_max_pool1d()
+            .dense(2, Activation::Softmax)
+            .build()
+            .unwrap();
+        // the step stays small so the largest position of every sequence does not move
+        let errors = gradient_check(&network, &input, &target, 3e-3).unwrap();
+        assert!(errors.iter().all(|e| *e < 1e-2), "{:?}", errors);
+    }
+}
@@ -0,0 +1,90 @@
+use super::error::Error;
+use super::block::BlockGradients;
+
+// gradients of the loss for every layer after the input layer, output layer last,
+// computed by Network::compute_gradients and applied by Network::apply_gradients
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradients {
+    // loss of the batch the gradients were computed on
+    pub loss: f32,
+    pub layers: Vec<BlockGradients>,
+}
+
+impl Gradients {
+    fn check(&self, other: &Gradients) -> Result<(), Error> {
+        if self.layers.len() != other.layers.len() {
+            return Err(Error::ShapeMismatch {
+                layer: self.layers.len().min(other.layers.len()) + 1,
+                expected: self.layers.len(),
+                found: other.layers.len(),
+            });
+        }
+        for (n, (a, b)) in self.layers.iter().zip(other.layers.iter()).enumerate() {
+            let (a, b) = (a.arrays(), b.arrays());
+            if a.len() != b.len() || a.iter().zip(b.iter()).any(|(a, b)| a.shape() != b.shape()) {
+                return Err(Error::ShapeMismatch {
+                    layer: n + 1,
+                    expected: a.iter().map(|a| a.len()).sum(),
+                    found: b.iter().map(|b| b.len()).sum(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // sum the gradients and losses of other into self, e.g. to accumulate over several batches
+    pub fn add(&mut self, other: &Gradients) -> Result<(), Error> {
+        self.check(other)?;
+        self.loss += other.loss;
+        for (a, b) in self.layers.iter_mut().zip(other.layers.iter()) {
+            for (mut a, b) in a.arrays_mut().into_iter().zip(b.arrays()) {
+                a += &b;
+            }
+        }
+        Ok(())
+    }
+
+    // multiply gradients and loss by factor, e.g. 1 / n after adding n gradients
+    pub fn scale(&mut self, factor: f32) {
+        self.loss *= factor;
+        for layer in self.layers.iter_mut() {
+            for mut array in layer.arrays_mut() {
+                array *= factor;
+            }
+        }
+    }
+
+    // the mean of several gradients, None if there are none
+    pub fn mean(gradients: &[Gradients]) -> Result<Option<Gradients>, Error> {
+        let Some((first, rest)) = gradients.split_first() else {
+            return Ok(None);
+        };
+        let mut mean = first.clone();
+        for gradients in rest {
+            mean.add(gradients)?;
+        }
+        mean.scale(1.0 / gradients.len() as f32);
+        Ok(Some(mean))
+    }
+
+    // euclidean norm over all parameters
+    pub fn norm(&self) -> f32 {
+        self.layers
+            .iter()
+            .flat_map(|l| l.arrays())
+            .map(|a| a.fold(0.0, |s, g| s + g * g))
+            .sum::<f32>()
+            .sqrt()
+    }
+
+    // rescale so the norm is at most max_norm, returns the norm before clipping
+    pub fn clip_norm(&mut self, max_norm: f32) -> f32 {
+        let norm = self.norm();
+        if norm > max_norm {
+            let loss = self.loss;
+            self.scale(max_norm / norm);
+            self.loss = loss;
+        }
+        norm
+    }
+}
@@ -0,0 +1,188 @@
+// reader for the IDX format: two zero bytes, a type byte and the number of dimensions,
+// then one big-endian u32 per dimension and the values in row-major order
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use ndarray::{Array2, ArrayD, IxDyn};
+
+use super::dataset::Dataset;
+use super::error::Error;
+
+const UNSIGNED_BYTE: u8 = 0x08;
+const FLOAT: u8 = 0x0D;
+
+fn read_bytes<R: Read>(reader: &mut R, len: usize) -> Result<Vec<u8>, Error> {
+    let mut bytes = vec![0; len];
+    reader
+        .read_exact(&mut bytes)
+        .map_err(|_| Error::Format("idx file is truncated".to_string()))?;
+    Ok(bytes)
+}
+
+// reads exactly len payload bytes without trusting len for the allocation
+fn read_payload<R: Read>(reader: &mut R, len: usize) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(Error::Format("idx file is truncated".to_string()));
+    }
+    Ok(bytes)
+}
+
+// the data type byte and the values of an idx stream
+fn read_typed<R: Read>(mut reader: R) -> Result<(u8, ArrayD<f32>), Error> {
+    let magic = read_bytes(&mut reader, 4)?;
+    if magic[0] != 0 || magic[1] != 0 {
+        return Err(Error::Format(format!("bad idx magic number {:02x?}", magic)));
+    }
+    let (data_type, dims) = (magic[2], magic[3] as usize);
+
+    let mut shape = Vec::with_capacity(dims);
+    for _ in 0..dims {
+        let dim = read_bytes(&mut reader, 4)?;
+        shape.push(u32::from_be_bytes([dim[0], dim[1], dim[2], dim[3]]) as usize);
+    }
+    // the header is untrusted, its dimensions may not even fit a usize
+    let overflow = || Error::Format(format!("idx dimensions {:?} are too large", shape));
+    let len = shape
+        .iter()
+        .try_fold(1usize, |len, dim| len.checked_mul(*dim))
+        .ok_or_else(overflow)?;
+
+    let values = match data_type {
+        UNSIGNED_BYTE => read_payload(&mut reader, len)?.into_iter().map(f32::from).collect(),
+        FLOAT => read_payload(&mut reader, len.checked_mul(4).ok_or_else(overflow)?)?
+            .chunks_exact(4)
+            .map(|b| f32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+        other => return Err(Error::Format(format!("unsupported idx data type 0x{:02x}", other))),
+    };
+    Ok((data_type, ArrayD::from_shape_vec(IxDyn(&shape), values).unwrap()))
+}
+
+// u8 values stay in 0..=255, no scaling is done here
+pub fn read_idx<R: Read>(reader: R) -> Result<ArrayD<f32>, Error> {
+    Ok(read_typed(reader)?.1)
+}
+
+pub fn read_idx_file<P: AsRef<Path>>(path: P) -> Result<ArrayD<f32>, Error> {
+    read_idx(BufReader::new(File::open(path)?))
+}
+
+impl Dataset {
+    // pairs an idx file of images with an idx file of u8 labels, e.g. mnist, every image is flattened
+    // into a row, u8 pixels scaled by 1/255 and float ones kept, and every label one-hot encoded over classes
+    pub fn from_idx<P: AsRef<Path>, Q: AsRef<Path>>(images: P, labels: Q, classes: usize) -> Result<Self, Error> {
+        let (image_type, images) = read_typed(BufReader::new(File::open(images)?))?;
+        let (label_type, labels) = read_typed(BufReader::new(File::open(labels)?))?;
+        if label_type != UNSIGNED_BYTE {
+            return Err(Error::Format(format!("idx labels must be unsigned bytes, found type 0x{:02x}", label_type)));
+        }
+        if images.ndim() == 0 || labels.ndim() != 1 {
+            return Err(Error::Format(format!(
+                "expected images with a sample dimension and one dimensional labels, found {:?} and {:?}",
+                images.shape(),
+                labels.shape()
+            )));
+        }
+
+        let samples = images.shape()[0];
+        let pixels = images.len() / samples.max(1);
+        let mut features = images.into_shape((samples, pixels)).unwrap();
+        if image_type == UNSIGNED_BYTE {
+            features.mapv_inplace(|v| v / 255.0);
+        }
+
+        let mut targets = Array2::zeros((labels.len(), classes));
+        for (row, label) in labels.iter().enumerate() {
+            let class = *label as usize;
+            if class >= classes {
+                return Err(Error::Format(format!("label {} out of range for {} classes", label, classes)));
+            }
+            targets[[row, class]] = 1.0;
+        }
+        Self::new(features, targets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use ndarray::{array, Ix2};
+
+    use crate::net::layer::InputLayer;
+
+    use super::*;
+
+    fn idx(data_type: u8, shape: &[u32], payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0, 0, data_type, shape.len() as u8];
+        for dim in shape {
+            bytes.extend_from_slice(&dim.to_be_bytes());
+        }
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    #[test]
+    fn read_idx_test() {
+        let bytes = idx(UNSIGNED_BYTE, &[2, 3], &[0, 1, 2, 253, 254, 255]);
+        let array = read_idx(bytes.as_slice()).unwrap().into_dimensionality::<Ix2>().unwrap();
+        assert_eq!(array![[0.0, 1.0, 2.0], [253.0, 254.0, 255.0]], array);
+
+        let payload: Vec<u8> = [1.5f32, -2.0].iter().flat_map(|v| v.to_be_bytes()).collect();
+        let array = read_idx(idx(FLOAT, &[2], &payload).as_slice()).unwrap();
+        assert_eq!(vec![1.5, -2.0], array.into_raw_vec());
+
+        assert!(matches!(read_idx(&[1u8, 0, 8, 1][..]), Err(Error::Format(_))));
+        assert!(matches!(read_idx(idx(0x0B, &[1], &[0, 0]).as_slice()), Err(Error::Format(_))));
+        assert!(matches!(read_idx(idx(UNSIGNED_BYTE, &[4], &[0, 0]).as_slice()), Err(Error::Format(_))));
+        // a header claiming more than fits in memory or in a usize fails without allocating it
+        let huge = idx(FLOAT, &[u32::MAX, u32::MAX, u32::MAX], &[0, 0]);
+        assert!(matches!(read_idx(huge.as_slice()), Err(Error::Format(_))));
+        let large = idx(UNSIGNED_BYTE, &[u32::MAX, 16], &[0, 0]);
+        assert!(matches!(read_idx(large.as_slice()), Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn from_idx_test() {
+        let dir = std::env::temp_dir();
+        let images = dir.join(format!("firstnet-{}-images.idx", std::process::id()));
+        let labels = dir.join(format!("firstnet-{}-labels.idx", std::process::id()));
+        // three 2x2 images
+        fs::write(&images, idx(UNSIGNED_BYTE, &[3, 2, 2], &[0, 255, 51, 0, 255, 255, 255, 255, 0, 0, 0, 0])).unwrap();
+        fs::write(&labels, idx(UNSIGNED_BYTE, &[3], &[2, 0, 1])).unwrap();
+
+        let dataset = Dataset::from_idx(&images, &labels, 3);
+        let out_of_range = Dataset::from_idx(&images, &labels, 2);
+        fs::remove_file(&images).unwrap();
+        fs::remove_file(&labels).unwrap();
+
+        let dataset = dataset.unwrap();
+        assert_eq!(
+            array![[0.0, 1.0, 0.2, 0.0], [1.0, 1.0, 1.0, 1.0], [0.0, 0.0, 0.0, 0.0]],
+            dataset.features
+        );
+        assert_eq!(array![[0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], dataset.targets);
+        assert!(matches!(out_of_range, Err(Error::Format(_))));
+
+        // float images are taken as they are, float labels are refused
+        let payload: Vec<u8> = [0.5f32, -2.0, 3.0, 7.5].iter().flat_map(|v| v.to_be_bytes()).collect();
+        fs::write(&images, idx(FLOAT, &[2, 2], &payload)).unwrap();
+        fs::write(&labels, idx(UNSIGNED_BYTE, &[2], &[1, 0])).unwrap();
+        let floats = Dataset::from_idx(&images, &labels, 2);
+        fs::write(&labels, idx(FLOAT, &[2], &payload[..8])).unwrap();
+        let float_labels = Dataset::from_idx(&images, &labels, 2);
+        fs::remove_file(&images).unwrap();
+        fs::remove_file(&labels).unwrap();
+        assert_eq!(array![[0.5, -2.0], [3.0, 7.5]], floats.unwrap().features);
+        assert!(matches!(float_labels, Err(Error::Format(_))));
+
+        let (image, label) = dataset.sample(2).unwrap();
+        let mut input = InputLayer::new(4);
+        input.set_inputs(image.to_vec()).unwrap();
+        assert_eq!(array![0.0, 1.0, 0.0], label);
+        assert!(dataset.sample(3).is_none());
+    }
+}
@@ -1,314 +1,628 @@
-use std::any::Any;
 use ndarray::prelude::*;
+use rand::Rng;
 use std::fmt;
-use tracing::instrument::WithSubscriber;
+use std::ops::{Deref, DerefMut};
 
-use crate::net::neuron::{Neuron, NeuronBase};
-
-use super::neuron::{Bias, Hidden, Input, Output};
+use crate::net::activation_functions::Activation;
+use crate::net::error::Error;
+use crate::net::neuron::{Hidden, Input, Neuron, Output};
+use crate::net::optimizers::Optimizer;
+use crate::net::regularizers::Regularizer;
+use crate::net::weight_functions::Initializer;
 
 pub trait Layer {
     // return the number of weights
     fn len_weights(&self) -> u32;
-    fn values_as_arr(&self) -> Array1<f32>;
+
+    // return the output values of the last forward pass, one row per sample
+    fn batch_values(&self) -> ArrayView2<'_, f32>;
+
+    // return the output values of the first sample of the last forward pass
+    fn values_as_arr(&self) -> Array1<f32> {
+        self.batch_values().row(0).to_owned()
+    }
 
     // return the number of neurons
     fn len(&self) -> usize;
 
-    // return the neuron at index
-    fn get(&self, index: usize) -> Option<&Neuron>;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 
-    // return mutable reference to neuron at index
-    fn get_mut(&mut self, index: usize) -> Option<&mut Neuron>;
+    // return a view of the neuron at index
+    fn get(&self, index: usize) -> Option<Neuron>;
 
-    // return all neurons
-    fn get_all(&self) -> &Array1<Neuron>;
+    // return views of all neurons
+    fn get_all(&self) -> Array1<Neuron> {
+        (0..self.len()).filter_map(|n| self.get(n)).collect()
+    }
 
-    // return mutable reference to all neurons
-    fn get_all_mut(&mut self) -> &mut Array1<Neuron>;
+    fn get_activation(&self) -> Activation;
+}
+
+// a view of the neuron at index of a layer without weights
+pub(crate) fn weightless_neuron(input_values: &Array2<f32>, output_values: &Array2<f32>, index: usize) -> Option<Neuron> {
+    if index >= output_values.ncols() {
+        return None;
+    }
+    Some(Neuron::Hidden(Hidden {
+        input_value: input_values[[0, index]],
+        output_value: output_values[[0, index]],
+        weights: Array1::zeros(0),
+    }))
+}
 
-    fn get_activation_derivation(&self) -> fn(f32) -> f32;
+// gradients of a dense layer averaged over all samples of the batch
+#[derive(Debug, Clone, PartialEq)]
+pub struct DenseGradients {
+    pub weights: Array2<f32>,
+    // empty for a layer without bias
+    pub biases: Array1<f32>,
+}
+
+impl DenseGradients {
+    fn new(prev_values: &ArrayView2<f32>, deltas: &Array2<f32>, bias: bool) -> Self {
+        let batch_size = deltas.nrows() as f32;
+        let biases = match bias {
+            true => deltas.sum_axis(Axis(0)) / batch_size,
+            false => Array1::zeros(0),
+        };
+        Self {
+            weights: prev_values.t().dot(deltas) / batch_size,
+            biases,
+        }
+    }
+
+    // the gradients of the trainable parameters, the weights followed by the biases if there are any
+    pub fn arrays(&self) -> Vec<ArrayViewD<'_, f32>> {
+        let mut arrays = vec![self.weights.view().into_dyn()];
+        if !self.biases.is_empty() {
+            arrays.push(self.biases.view().into_dyn());
+        }
+        arrays
+    }
+
+    pub fn arrays_mut(&mut self) -> Vec<ArrayViewMutD<'_, f32>> {
+        let mut arrays = vec![self.weights.view_mut().into_dyn()];
+        if !self.biases.is_empty() {
+            arrays.push(self.biases.view_mut().into_dyn());
+        }
+        arrays
+    }
+}
+
+// the trainable parameters of a dense layer in the order of DenseGradients::arrays
+pub(crate) fn dense_params_mut<'a>(weights: &'a mut Array2<f32>, biases: &'a mut Array1<f32>, bias: bool) -> Vec<ArrayViewMutD<'a, f32>> {
+    let mut params = vec![weights.view_mut().into_dyn()];
+    if bias {
+        params.push(biases.view_mut().into_dyn());
+    }
+    params
+}
+
+// hand both parameters of a dense layer to the optimizer, layer identifies them across steps,
+// the decoupled weight decay comes before the step and the constraints after it
+pub(crate) fn apply_dense(
+    weights: &mut Array2<f32>,
+    biases: &mut Array1<f32>,
+    bias: bool,
+    regularizer: &Regularizer,
+    gradients: &DenseGradients,
+    optimizer: &mut dyn Optimizer,
+    layer: usize,
+) {
+    regularizer.decay(optimizer.learning_rate(), weights, biases);
+    optimizer.update((layer, 0), weights.view_mut().into_dyn(), gradients.weights.view().into_dyn());
+    if bias {
+        optimizer.update((layer, 1), biases.view_mut().into_dyn(), gradients.biases.view().into_dyn());
+    }
+    regularizer.constrain(weights, biases);
 }
 
 #[derive(Debug, Clone)]
 pub struct InputLayer {
-    pub inputs: Array1<Neuron>,
-    pub has_bias: bool,
+    // (batch_size, layer_size)
+    pub values: Array2<f32>,
 }
 
 impl Layer for InputLayer {
     fn len_weights(&self) -> u32 {
-        match self.inputs.first().unwrap() {
-            Neuron::Input(i) => 0,
-            _ => {0}
-        }
+        0
     }
 
-    fn values_as_arr(&self) -> Array1<f32> {
-        Array1::from_vec(
-            self.inputs
-                .iter()
-                .map(|n| match n {
-                    Neuron::Input(i) => i.input_value,
-                    Neuron::Bias(b) => b.output_value,
-                    _ => 0.0,
-                })
-                .collect(),
-        )
+    fn batch_values(&self) -> ArrayView2<'_, f32> {
+        self.values.view()
     }
 
     fn len(&self) -> usize {
-        self.inputs.len()
+        self.values.ncols()
     }
 
-    fn get(&self, index: usize) -> Option<&Neuron> {
-        self.inputs.get(index)
+    fn get(&self, index: usize) -> Option<Neuron> {
+        self.values.get((0, index)).map(|v| {
+            Neuron::Input(Input {
+                input_value: *v,
+                output_value: *v,
+                weights: Array1::zeros(0),
+            })
+        })
     }
 
-    fn get_mut(&mut self, index: usize) -> Option<&mut Neuron> {
-        self.inputs.get_mut(index)
+    fn get_activation(&self) -> Activation {
+        Activation::Identity
     }
+}
 
-    fn get_all(&self) -> &Array1<Neuron> {
-        &self.inputs
+impl InputLayer {
+    pub fn new(layer_size: u32) -> Self {
+        Self {
+            values: Array2::zeros((1, layer_size as usize)),
+        }
+    }
+
+    pub fn set_inputs(&mut self, input_values: Vec<f32>) -> Result<(), Error> {
+        self.set_batch(&Array1::from_vec(input_values).insert_axis(Axis(0)))
     }
 
-    fn get_all_mut(&mut self) -> &mut Array1<Neuron> {
-        &mut self.inputs
+    pub fn set_batch(&mut self, input_values: &Array2<f32>) -> Result<(), Error> {
+        self.check(input_values)?;
+        self.values = input_values.clone();
+        Ok(())
     }
 
-    fn get_activation_derivation(&self) -> fn(f32) -> f32 {
-        |x| x
+    // the batch must be non empty, finite and have one column per neuron
+    pub fn check(&self, input_values: &Array2<f32>) -> Result<(), Error> {
+        if input_values.nrows() == 0 {
+            return Err(Error::EmptyBatch);
+        }
+        if input_values.ncols() != self.len() {
+            return Err(Error::ShapeMismatch {
+                layer: 0,
+                expected: self.len(),
+                found: input_values.ncols(),
+            });
+        }
+        if !input_values.iter().all(|v| v.is_finite()) {
+            return Err(Error::NonFinite { layer: 0 });
+        }
+        Ok(())
     }
 }
 
-impl InputLayer {
-    fn from_inputs(inputs: Array1<Neuron>, has_bias: bool) -> Self {
-        Self { inputs, has_bias }
+impl fmt::Display for InputLayer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "values: {}", self.values)
     }
+}
 
-    pub fn new(mut layer_size: u32, bias: bool) -> Self {
-        let mut inputs = vec![Neuron::Input(Input { input_value: 0.0, output_value: 0.0, weights: Array1::zeros(0) }); layer_size.try_into().unwrap()];
-        if bias {
-            inputs.push(Neuron::Bias(Bias::new()));
-        }
+// the weights, biases and cached values shared by the hidden and the output dense layers,
+// both wrap one and reach its fields and methods through Deref
+#[derive(Debug, Clone)]
+pub struct DenseCore {
+    // (fan_in, layer_size), column n holds the incoming weights of neuron n
+    pub weights: Array2<f32>,
+    pub biases: Array1<f32>,
+    // (batch_size, layer_size) values of the last forward pass
+    pub input_values: Array2<f32>,
+    pub output_values: Array2<f32>,
+    pub activation: Activation,
+    pub bias: bool,
+    pub regularizer: Regularizer,
+}
+
+impl DenseCore {
+    fn with_weights(weights: Array2<f32>, bias: bool, activation: Activation) -> Self {
+        let layer_size = weights.ncols();
         Self {
-            inputs: Array1::from_vec(inputs),
-            has_bias: bias,
+            weights,
+            biases: Array1::zeros(layer_size),
+            input_values: Array2::zeros((1, layer_size)),
+            output_values: Array2::zeros((1, layer_size)),
+            activation,
+            bias,
+            regularizer: Regularizer::default(),
         }
     }
 
-    pub fn set_inputs(&mut self, input_values: Vec<f32>) {
-        let mut c = self.inputs.len();
-        if self.has_bias {
-            c -= 1;
-        }
-        assert_eq!(c, input_values.len());
+    // weights drawn from rng by the default initializer, glorot uniform
+    pub fn new<R: Rng + ?Sized>(
+        layer_size: u32,
+        bias: bool,
+        activation: Activation,
+        rng: &mut R,
+        prev_layer: &dyn Layer,
+    ) -> Self {
+        Self::with_initializer(layer_size, bias, activation, Initializer::default(), rng, prev_layer)
+    }
+
+    // weights drawn by initializer with the fan-in of prev_layer and the fan-out layer_size
+    pub fn with_initializer<R: Rng + ?Sized>(
+        layer_size: u32,
+        bias: bool,
+        activation: Activation,
+        initializer: Initializer,
+        rng: &mut R,
+        prev_layer: &dyn Layer,
+    ) -> Self {
+        let weights = initializer.init(prev_layer.len(), layer_size as usize, rng);
+        Self::with_weights(weights, bias, activation)
+    }
+
+    pub fn forward(&mut self, inputs: &ArrayView2<f32>) {
+        (self.input_values, self.output_values) = self.compute(inputs);
+    }
 
-        for i in 0..c {
-            let mut input = self.inputs.get_mut(i).unwrap();
-            input.set_input_value(input_values[i]);
+    // (pre-activations, outputs) for the inputs without touching the cached values
+    pub fn compute(&self, inputs: &ArrayView2<f32>) -> (Array2<f32>, Array2<f32>) {
+        let x = inputs.dot(&self.weights) + &self.biases;
+        let outputs = self.activation.activate(&x);
+        (x, outputs)
+    }
+
+    // gradients for the given (batch_size, layer_size) deltas including those of the l1 and l2 penalties
+    pub fn gradients(&self, prev_values: &ArrayView2<f32>, deltas: &Array2<f32>) -> DenseGradients {
+        let mut gradients = DenseGradients::new(prev_values, deltas, self.bias);
+        self.regularizer
+            .add_gradients(&self.weights, &self.biases, self.bias, &mut gradients);
+        gradients
+    }
+
+    // deltas propagated to the previous layer (before its activation derivative)
+    pub fn propagate(&self, deltas: &Array2<f32>) -> Array2<f32> {
+        deltas.dot(&self.weights.t())
+    }
+
+    pub fn apply(&mut self, gradients: &DenseGradients, optimizer: &mut dyn Optimizer, layer: usize) {
+        apply_dense(
+            &mut self.weights,
+            &mut self.biases,
+            self.bias,
+            &self.regularizer,
+            gradients,
+            optimizer,
+            layer,
+        );
+    }
+
+    pub(crate) fn params_mut(&mut self) -> Vec<ArrayViewMutD<'_, f32>> {
+        dense_params_mut(&mut self.weights, &mut self.biases, self.bias)
+    }
+
+    // (input value, output value, incoming weights) of the neuron at index
+    fn neuron(&self, index: usize) -> Option<(f32, f32, Array1<f32>)> {
+        if index >= self.biases.len() {
+            return None;
         }
+        Some((
+            self.input_values[[0, index]],
+            self.output_values[[0, index]],
+            self.weights.column(index).to_owned(),
+        ))
     }
 }
 
-impl fmt::Display for InputLayer {
+impl fmt::Display for DenseCore {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "neurons: {:?}\n", self.inputs)
+        writeln!(f, "weights: {}\nbiases: {}", self.weights, self.biases)
     }
 }
 
+// the last layer of a network, its outputs are those of the network
 #[derive(Debug, Clone)]
 pub struct OutputLayer {
-    pub outputs: Array1<Neuron>,
-    pub activation_function: fn(f32) -> f32,
-    pub activation_derivation: fn(f32) -> f32,
+    pub core: DenseCore,
 }
 
 impl OutputLayer {
-    pub fn new(
+    // weights drawn from rng by the default initializer, glorot uniform
+    pub fn new<R: Rng + ?Sized>(
         layer_size: u32,
-        activation_function: fn(f32) -> f32,
-        activation_derivation: fn(f32) -> f32,
-        weight_function: fn(u32) -> Array1<f32>,
+        bias: bool,
+        activation: Activation,
+        rng: &mut R,
         prev_layer: &dyn Layer,
     ) -> Self {
-        let weights = weight_function(prev_layer.len_weights());
-        let mut outputs: Vec<Neuron> = vec![
-            Neuron::Output(Output {
-                input_value: 0.0,
-                output_value: 0.0,
-                weights
-            });
-            layer_size.try_into().unwrap()
-        ];
         Self {
-            outputs : Array1::from_vec(outputs),
-            activation_function,
-            activation_derivation,
+            core: DenseCore::new(layer_size, bias, activation, rng, prev_layer),
         }
     }
-}
 
-impl Layer for OutputLayer {
-    fn len_weights(&self) -> u32 {
-        match self.outputs.first().unwrap() {
-            Neuron::Output(o) => o.weights.len().try_into().unwrap(),
-            _ => {0}
+    // weights drawn by initializer with the fan-in of prev_layer and the fan-out layer_size
+    pub fn with_initializer<R: Rng + ?Sized>(
+        layer_size: u32,
+        bias: bool,
+        activation: Activation,
+        initializer: Initializer,
+        rng: &mut R,
+        prev_layer: &dyn Layer,
+    ) -> Self {
+        Self {
+            core: DenseCore::with_initializer(layer_size, bias, activation, initializer, rng, prev_layer),
         }
     }
+}
 
-    fn values_as_arr(&self) -> Array1<f32> {
-        Array1::from_vec(
-            self.outputs
-                .iter()
-                .map(|n| match n {
-                    Neuron::Input(i) => i.input_value,
-                    _ => 0.0,
-                })
-                .collect(),
-        )
-    }
+impl Deref for OutputLayer {
+    type Target = DenseCore;
 
-    fn len(&self) -> usize {
-        self.outputs.len()
+    fn deref(&self) -> &DenseCore {
+        &self.core
     }
+}
 
-
-    fn get(&self, index: usize) -> Option<&Neuron> {
-        self.outputs.get(index)
+impl DerefMut for OutputLayer {
+    fn deref_mut(&mut self) -> &mut DenseCore {
+        &mut self.core
     }
+}
 
-    fn get_mut(&mut self, index: usize) -> Option<&mut Neuron> {
-        self.outputs.get_mut(index)
+impl Layer for OutputLayer {
+    fn len_weights(&self) -> u32 {
+        self.weights.len() as u32
     }
 
-    fn get_all(&self) -> &Array1<Neuron> {
-        &self.outputs
+    fn batch_values(&self) -> ArrayView2<'_, f32> {
+        self.output_values.view()
     }
 
+    fn len(&self) -> usize {
+        self.biases.len()
+    }
 
-    fn get_all_mut(&mut self) -> &mut Array1<Neuron> {
-        &mut self.outputs
+    fn get(&self, index: usize) -> Option<Neuron> {
+        let (input_value, output_value, weights) = self.core.neuron(index)?;
+        Some(Neuron::Output(Output {
+            input_value,
+            output_value,
+            weights,
+        }))
     }
 
-    fn get_activation_derivation(&self) -> fn(f32) -> f32 {
-       self.activation_derivation
+    fn get_activation(&self) -> Activation {
+        self.activation
     }
 }
 
 impl fmt::Display for OutputLayer {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "neurons: {:?}\n", self.outputs)
+        self.core.fmt(f)
     }
 }
 
+// a fully connected layer between the input and the output layer
 #[derive(Debug, Clone)]
 pub struct HiddenLayer {
-    pub neurons: Array1<Neuron>,
-    pub activation_function: fn(f32) -> f32,
-    pub activation_derivation: fn(f32) -> f32,
-    pub bias: bool,
+    pub core: DenseCore,
 }
 
 impl HiddenLayer {
-    pub fn new(
+    // weights drawn from rng by the default initializer, glorot uniform
+    pub fn new<R: Rng + ?Sized>(
         layer_size: u32,
         bias: bool,
-        activation_function: fn(f32) -> f32,
-        activation_derivation: fn(f32) -> f32,
-        weight_function: fn(u32) -> Array1<f32>,
+        activation: Activation,
+        rng: &mut R,
         prev_layer: &dyn Layer,
     ) -> Self {
-        let weights = weight_function(prev_layer.len_weights());
-        let mut neurons: Vec<Neuron> = vec![
-            Neuron::Hidden(Hidden {
-                input_value: 0.0,
-                output_value: 0.0,
-                weights
-            });
-            layer_size.try_into().unwrap()
-        ];
-        if bias {
-            neurons.push(Neuron::Bias(Bias { input_value: 1.0, output_value: 1.0, weights: Array1::zeros(0)}));
-
+        Self {
+            core: DenseCore::new(layer_size, bias, activation, rng, prev_layer),
         }
+    }
+
+    // weights drawn by initializer with the fan-in of prev_layer and the fan-out layer_size
+    pub fn with_initializer<R: Rng + ?Sized>(
+        layer_size: u32,
+        bias: bool,
+        activation: Activation,
+        initializer: Initializer,
+        rng: &mut R,
+        prev_layer: &dyn Layer,
+    ) -> Self {
         Self {
-            neurons : Array1::from_vec(neurons),
-            activation_function,
-            activation_derivation,
-            bias,
+            core: DenseCore::with_initializer(layer_size, bias, activation, initializer, rng, prev_layer),
         }
     }
 }
 
-impl Layer for HiddenLayer{
+impl Deref for HiddenLayer {
+    type Target = DenseCore;
+
+    fn deref(&self) -> &DenseCore {
+        &self.core
+    }
+}
+
+impl DerefMut for HiddenLayer {
+    fn deref_mut(&mut self) -> &mut DenseCore {
+        &mut self.core
+    }
+}
+
+impl Layer for HiddenLayer {
     fn len_weights(&self) -> u32 {
-        match self.neurons.first().unwrap() {
-            Neuron::Hidden(h) => h.weights.len().try_into().unwrap(),
-            _ => {0}
-        }
+        self.weights.len() as u32
     }
 
-    fn values_as_arr(&self) -> Array1<f32> {
-        Array1::from_vec(
-            self.neurons
-                .iter()
-                .map(|n| match n {
-                    Neuron::Hidden(i) => i.input_value,
-                    Neuron::Bias(b) => b.output_value,
-                    _ => 0.0,
-                })
-                .collect(),
-        )
+    fn batch_values(&self) -> ArrayView2<'_, f32> {
+        self.output_values.view()
     }
 
     fn len(&self) -> usize {
-        self.neurons.len()
+        self.biases.len()
     }
 
-    fn get(&self, index: usize) -> Option<&Neuron> {
-        self.neurons.get(index)
+    fn get(&self, index: usize) -> Option<Neuron> {
+        let (input_value, output_value, weights) = self.core.neuron(index)?;
+        Some(Neuron::Hidden(Hidden {
+            input_value,
+            output_value,
+            weights,
+        }))
     }
 
-    fn get_mut(&mut self, index: usize) -> Option<&mut Neuron> {
-        self.neurons.get_mut(index)
+    fn get_activation(&self) -> Activation {
+        self.activation
     }
+}
 
-    fn get_all(&self) -> &Array1<Neuron> {
-        &self.neurons
+impl fmt::Display for HiddenLayer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.core.fmt(f)
+    }
+}
+
+// an activation on its own, e.g. behind a batch normalisation of a dense layer with the identity activation
+#[derive(Debug, Clone)]
+pub struct ActivationLayer {
+    pub activation: Activation,
+    // (batch_size, layer_size) values of the last forward pass
+    pub input_values: Array2<f32>,
+    pub output_values: Array2<f32>,
+}
+
+impl ActivationLayer {
+    // keeps the size of prev_layer
+    pub fn new(activation: Activation, prev_layer: &dyn Layer) -> Self {
+        let layer_size = prev_layer.len();
+        Self {
+            activation,
+            input_values: Array2::zeros((1, layer_size)),
+            output_values: Array2::zeros((1, layer_size)),
+        }
     }
 
-    fn get_all_mut(&mut self) -> &mut Array1<Neuron> {
-        &mut self.neurons
+    pub fn forward(&mut self, inputs: &ArrayView2<f32>) {
+        self.input_values = inputs.to_owned();
+        self.output_values = self.activation.activate(&self.input_values);
     }
 
-    fn get_activation_derivation(&self) -> fn(f32) -> f32 {
-        self.activation_derivation
+    // deltas of the outputs propagated to the inputs
+    pub fn propagate(&self, deltas: &Array2<f32>) -> Array2<f32> {
+        self.activation
+            .backpropagate(&self.input_values, &self.output_values, deltas)
     }
 }
 
-impl fmt::Display for HiddenLayer {
+impl Layer for ActivationLayer {
+    fn len_weights(&self) -> u32 {
+        0
+    }
+
+    fn batch_values(&self) -> ArrayView2<'_, f32> {
+        self.output_values.view()
+    }
+
+    fn len(&self) -> usize {
+        self.output_values.ncols()
+    }
+
+    fn get(&self, index: usize) -> Option<Neuron> {
+        weightless_neuron(&self.input_values, &self.output_values, index)
+    }
+
+    fn get_activation(&self) -> Activation {
+        self.activation
+    }
+}
+
+impl fmt::Display for ActivationLayer {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "neurons: {:?}\n", self.neurons)
+        writeln!(f, "activation: {}", self.activation)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::net::{
-        activation_functions::{self, sigmoid},
-        weight_functions::xavier_init,
-    };
-    use crate::net::activation_functions::sigmoid_derivative;
+    use crate::net::activation_functions::sigmoid;
+    use crate::net::neuron::NeuronBase;
+    use crate::net::optimizers::Sgd;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
 
     use super::*;
 
     #[test]
     fn layer_test() {
-        let input = InputLayer::new(2, true);
-        let a = HiddenLayer::new(5, true, sigmoid, sigmoid_derivative, xavier_init, &input);
-        println!("{:?}", a);
-        assert_eq!(1, 0);
+        let input = InputLayer::new(2);
+        let a = HiddenLayer::new(5, true, Activation::Sigmoid, &mut StdRng::seed_from_u64(0), &input);
+        assert_eq!((2, 5), a.weights.dim());
+        assert_eq!(5, a.biases.len());
+        assert_eq!(5, a.len());
+        assert_eq!(10, a.len_weights());
+
+        let b = OutputLayer::new(3, true, Activation::Sigmoid, &mut StdRng::seed_from_u64(0), &a);
+        assert_eq!((5, 3), b.weights.dim());
+    }
+
+    #[test]
+    fn neuron_view_test() {
+        let input = InputLayer::new(2);
+        let mut a = HiddenLayer::new(2, true, Activation::Sigmoid, &mut StdRng::seed_from_u64(0), &input);
+        a.weights = array![[1.0, 0.5], [-1.0, 0.25]];
+        a.biases = array![0.0, 0.5];
+        a.forward(&array![[2.0, 4.0]].view());
+
+        let neuron = a.get(1).unwrap();
+        assert_eq!(2.5, neuron.get_input_value());
+        assert_eq!(sigmoid(2.5), neuron.get_output_value());
+        assert_eq!(&array![0.5, 0.25], neuron.get_weights());
+        assert!(a.get(2).is_none());
+        assert_eq!(2, a.get_all().len());
+    }
+
+    #[test]
+    fn set_inputs_test() {
+        let mut input = InputLayer::new(2);
+        assert_eq!(Ok(()), input.set_inputs(vec![1.0, 2.0]));
+        assert_eq!(
+            Err(Error::ShapeMismatch { layer: 0, expected: 2, found: 3 }),
+            input.set_inputs(vec![1.0, 2.0, 3.0])
+        );
+        assert_eq!(Err(Error::NonFinite { layer: 0 }), input.set_inputs(vec![f32::NAN, 2.0]));
+        assert_eq!(Err(Error::EmptyBatch), input.set_batch(&Array2::zeros((0, 2))));
+        assert_eq!(array![1.0, 2.0], input.values_as_arr());
+    }
+
+    #[test]
+    fn activation_layer_test() {
+        let input = InputLayer::new(3);
+        let mut linear = HiddenLayer::new(3, true, Activation::Identity, &mut StdRng::seed_from_u64(0), &input);
+        let mut activation = ActivationLayer::new(Activation::Sigmoid, &linear);
+        let mut fused = linear.clone();
+        fused.activation = Activation::Sigmoid;
+        assert_eq!(3, activation.len());
+
+        // a linear layer followed by an activation layer is the fused layer split in two
+        let inputs = array![[1.0, -2.0, 0.5], [0.0, 1.0, 3.0]];
+        linear.forward(&inputs.view());
+        activation.forward(&linear.batch_values());
+        fused.forward(&inputs.view());
+        assert_eq!(fused.output_values, activation.output_values);
+
+        let deltas = array![[1.0, 0.5, -1.0], [2.0, 0.0, 1.0]];
+        let fused_deltas = fused.activation.backpropagate(&fused.input_values, &fused.output_values, &deltas);
+        assert_eq!(fused_deltas, activation.propagate(&deltas));
+    }
+
+    #[test]
+    fn batch_update_test() {
+        let input = InputLayer::new(2);
+        let mut a = HiddenLayer::new(1, true, Activation::Sigmoid, &mut StdRng::seed_from_u64(0), &input);
+        a.weights = array![[0.0], [0.0]];
+
+        let inputs = array![[1.0, 0.0], [0.0, 1.0]];
+        a.forward(&inputs.view());
+        assert_eq!((2, 1), a.output_values.dim());
+
+        // gradients are averaged over the batch
+        let deltas = array![[1.0], [3.0]];
+        let gradients = a.gradients(&inputs.view(), &deltas);
+        assert_eq!(array![[0.5], [1.5]], gradients.weights);
+        assert_eq!(array![2.0], gradients.biases);
+        assert_eq!((2, 2), a.propagate(&deltas).dim());
+
+        a.apply(&gradients, &mut Sgd::new(1.0), 0);
+        assert_eq!(array![[-0.5], [-1.5]], a.weights);
+        assert_eq!(array![-2.0], a.biases);
     }
 }
@@ -0,0 +1,347 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use ndarray::{Array2, Axis};
+
+use super::activation_functions::{softplus, Activation};
+
+// keeps log away from 0 for losses computed on probabilities
+const EPSILON: f32 = 1e-7;
+
+// outputs and expected are (batch_size, layer_size), value is averaged over the batch while
+// gradient returns the per sample gradients, the layers average them when updating
+pub trait Loss: Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // the name with the parameters, what a saved network stores, e.g. huber(0.5)
+    fn describe(&self) -> String {
+        self.name().to_string()
+    }
+
+    fn value(&self, outputs: &Array2<f32>, expected: &Array2<f32>) -> f32;
+
+    // gradient of the loss with respect to the outputs
+    fn gradient(&self, outputs: &Array2<f32>, expected: &Array2<f32>) -> Array2<f32>;
+
+    // some losses simplify when paired with their canonical output activation,
+    // return the loss computed from the pre-activations x and the gradient with respect to x
+    fn fused(
+        &self,
+        _activation: Activation,
+        _x: &Array2<f32>,
+        _outputs: &Array2<f32>,
+        _expected: &Array2<f32>,
+    ) -> Option<(f32, Array2<f32>)> {
+        None
+    }
+}
+
+fn batch_mean(values: Array2<f32>) -> f32 {
+    values.sum() / values.nrows() as f32
+}
+
+// mean of the squared differences over all outputs
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MeanSquaredError;
+
+impl Loss for MeanSquaredError {
+    fn name(&self) -> &'static str {
+        "mean_squared_error"
+    }
+
+    fn value(&self, outputs: &Array2<f32>, expected: &Array2<f32>) -> f32 {
+        let k = outputs.ncols() as f32;
+        batch_mean((outputs - expected).mapv(|d| d * d / k))
+    }
+
+    fn gradient(&self, outputs: &Array2<f32>, expected: &Array2<f32>) -> Array2<f32> {
+        let k = outputs.ncols() as f32;
+        (outputs - expected).mapv(|d| 2.0 * d / k)
+    }
+}
+
+// mean of the absolute differences over all outputs
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MeanAbsoluteError;
+
+impl Loss for MeanAbsoluteError {
+    fn name(&self) -> &'static str {
+        "mean_absolute_error"
+    }
+
+    fn value(&self, outputs: &Array2<f32>, expected: &Array2<f32>) -> f32 {
+        let k = outputs.ncols() as f32;
+        batch_mean((outputs - expected).mapv(|d| d.abs() / k))
+    }
+
+    fn gradient(&self, outputs: &Array2<f32>, expected: &Array2<f32>) -> Array2<f32> {
+        let k = outputs.ncols() as f32;
+        (outputs - expected).mapv(|d| if d == 0.0 { 0.0 } else { d.signum() / k })
+    }
+}
+
+// quadratic for differences up to delta and linear beyond, so outliers do not dominate
+#[derive(Debug, Clone, Copy)]
+pub struct Huber {
+    pub delta: f32,
+}
+
+impl Huber {
+    pub fn new(delta: f32) -> Self {
+        Self { delta }
+    }
+}
+
+impl Default for Huber {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl Loss for Huber {
+    fn name(&self) -> &'static str {
+        "huber"
+    }
+
+    fn describe(&self) -> String {
+        format!("{}({})", self.name(), self.delta)
+    }
+
+    fn value(&self, outputs: &Array2<f32>, expected: &Array2<f32>) -> f32 {
+        let k = outputs.ncols() as f32;
+        let delta = self.delta;
+        batch_mean((outputs - expected).mapv(|d| {
+            let l = if d.abs() <= delta {
+                0.5 * d * d
+            } else {
+                delta * (d.abs() - 0.5 * delta)
+            };
+            l / k
+        }))
+    }
+
+    fn gradient(&self, outputs: &Array2<f32>, expected: &Array2<f32>) -> Array2<f32> {
+        let k = outputs.ncols() as f32;
+        (outputs - expected).mapv(|d| d.clamp(-self.delta, self.delta) / k)
+    }
+}
+
+// for independent probabilities in (0, 1), paired with a sigmoid output layer
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BinaryCrossEntropy;
+
+impl Loss for BinaryCrossEntropy {
+    fn name(&self) -> &'static str {
+        "binary_cross_entropy"
+    }
+
+    fn value(&self, outputs: &Array2<f32>, expected: &Array2<f32>) -> f32 {
+        let k = outputs.ncols() as f32;
+        let mut total = 0.0;
+        for (p, y) in outputs.iter().zip(expected.iter()) {
+            let p = p.clamp(EPSILON, 1.0 - EPSILON);
+            total -= (y * p.ln() + (1.0 - y) * (1.0 - p).ln()) / k;
+        }
+        total / outputs.nrows() as f32
+    }
+
+    fn gradient(&self, outputs: &Array2<f32>, expected: &Array2<f32>) -> Array2<f32> {
+        let k = outputs.ncols() as f32;
+        let mut gradient = outputs - expected;
+        gradient.zip_mut_with(outputs, |g, p| {
+            let p = p.clamp(EPSILON, 1.0 - EPSILON);
+            *g /= p * (1.0 - p) * k;
+        });
+        gradient
+    }
+
+    fn fused(
+        &self,
+        activation: Activation,
+        x: &Array2<f32>,
+        outputs: &Array2<f32>,
+        expected: &Array2<f32>,
+    ) -> Option<(f32, Array2<f32>)> {
+        if activation != Activation::Sigmoid {
+            return None;
+        }
+        let k = outputs.ncols() as f32;
+        // -(y * log(sigmoid(x)) + (1 - y) * log(1 - sigmoid(x))) = softplus(x) - x * y
+        let losses = (x.mapv(softplus) - x * expected) / k;
+        Some((batch_mean(losses), (outputs - expected) / k))
+    }
+}
+
+// for rows of class probabilities, paired with a softmax output layer
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CategoricalCrossEntropy;
+
+impl Loss for CategoricalCrossEntropy {
+    fn name(&self) -> &'static str {
+        "categorical_cross_entropy"
+    }
+
+    fn value(&self, outputs: &Array2<f32>, expected: &Array2<f32>) -> f32 {
+        batch_mean(-(expected * &outputs.mapv(|p| p.max(EPSILON).ln())))
+    }
+
+    fn gradient(&self, outputs: &Array2<f32>, expected: &Array2<f32>) -> Array2<f32> {
+        -(expected / &outputs.mapv(|p| p.max(EPSILON)))
+    }
+
+    fn fused(
+        &self,
+        activation: Activation,
+        x: &Array2<f32>,
+        outputs: &Array2<f32>,
+        expected: &Array2<f32>,
+    ) -> Option<(f32, Array2<f32>)> {
+        if activation != Activation::Softmax {
+            return None;
+        }
+        Some((categorical_cross_entropy(x, expected), outputs - expected))
+    }
+}
+
+// cross entropy of softmax(logits) against one-hot (or soft) expected rows, averaged over the batch
+// computed from the logits with log-sum-exp so large values cannot overflow and log(0) cannot occur
+pub fn categorical_cross_entropy(logits: &Array2<f32>, expected: &Array2<f32>) -> f32 {
+    let mut total = 0.0;
+    for (z, y) in logits.axis_iter(Axis(0)).zip(expected.axis_iter(Axis(0))) {
+        let max = z.fold(f32::NEG_INFINITY, |m, v| m.max(*v));
+        let log_sum_exp = max + z.mapv(|v| (v - max).exp()).sum().ln();
+        // -sum(y * log_softmax(z))
+        total += y.iter().zip(z.iter()).map(|(y, z)| y * (log_sum_exp - z)).sum::<f32>();
+    }
+    total / logits.nrows() as f32
+}
+
+// the loss a network uses when none is configured
+pub fn default_loss(output_activation: Activation) -> Arc<dyn Loss> {
+    match output_activation {
+        Activation::Softmax => Arc::new(CategoricalCrossEntropy),
+        _ => Arc::new(MeanSquaredError),
+    }
+}
+
+// the built-in loss described by name, as Loss::describe writes it, used when loading a saved network,
+// a huber loss without its delta gets the default one
+pub fn loss_from_name(name: &str) -> Option<Arc<dyn Loss>> {
+    let (name, param) = match name.trim().split_once('(') {
+        Some((name, rest)) => (name, Some(rest.strip_suffix(')')?.trim().parse::<f32>().ok()?)),
+        None => (name.trim(), None),
+    };
+    let loss: Arc<dyn Loss> = match (name, param) {
+        ("mean_squared_error", None) => Arc::new(MeanSquaredError),
+        ("mean_absolute_error", None) => Arc::new(MeanAbsoluteError),
+        ("huber", None) => Arc::new(Huber::default()),
+        ("huber", Some(delta)) => Arc::new(Huber::new(delta)),
+        ("binary_cross_entropy", None) => Arc::new(BinaryCrossEntropy),
+        ("categorical_cross_entropy", None) => Arc::new(CategoricalCrossEntropy),
+        _ => return None,
+    };
+    Some(loss)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+
+    fn check_gradient(loss: &dyn Loss, outputs: &Array2<f32>, expected: &Array2<f32>) {
+        let eps = 1e-3;
+        let analytic = loss.gradient(outputs, expected);
+        for i in 0..outputs.len() {
+            let (r, c) = (i / outputs.ncols(), i % outputs.ncols());
+            let mut plus = outputs.clone();
+            plus[[r, c]] += eps;
+            let mut minus = outputs.clone();
+            minus[[r, c]] -= eps;
+            // value is a batch mean, gradient is per sample
+            let numeric = (loss.value(&plus, expected) - loss.value(&minus, expected)) / (2.0 * eps)
+                * outputs.nrows() as f32;
+            assert!(
+                (numeric - analytic[[r, c]]).abs() < 1e-2,
+                "{}: {} != {}",
+                loss.name(),
+                numeric,
+                analytic[[r, c]]
+            );
+        }
+    }
+
+    #[test]
+    fn gradient_test() {
+        let outputs = array![[0.2, 0.7, 0.1], [0.6, 0.3, 0.1]];
+        let expected = array![[0.0, 1.0, 0.0], [1.0, 0.0, 0.0]];
+        check_gradient(&MeanSquaredError, &outputs, &expected);
+        check_gradient(&MeanAbsoluteError, &outputs, &expected);
+        check_gradient(&Huber::new(0.5), &outputs, &expected);
+        check_gradient(&BinaryCrossEntropy, &outputs, &expected);
+        check_gradient(&CategoricalCrossEntropy, &outputs, &expected);
+    }
+
+    #[test]
+    fn value_test() {
+        let outputs = array![[0.5, 3.0]];
+        let expected = array![[0.0, 0.0]];
+        assert_eq!(4.625, MeanSquaredError.value(&outputs, &expected));
+        assert_eq!(1.75, MeanAbsoluteError.value(&outputs, &expected));
+        // 0.5 * 0.25 and 1.0 * (3.0 - 0.5)
+        assert_eq!(1.3125, Huber::new(1.0).value(&outputs, &expected));
+    }
+
+    #[test]
+    fn name_test() {
+        for loss in [
+            Arc::new(MeanSquaredError) as Arc<dyn Loss>,
+            Arc::new(MeanAbsoluteError),
+            Arc::new(Huber::new(0.5)),
+            Arc::new(BinaryCrossEntropy),
+            Arc::new(CategoricalCrossEntropy),
+        ] {
+            assert_eq!(loss.describe(), loss_from_name(&loss.describe()).unwrap().describe());
+        }
+        assert_eq!("huber(0.5)", Huber::new(0.5).describe());
+        assert_eq!("huber(1)", loss_from_name("huber").unwrap().describe());
+        assert!(loss_from_name("huber(x)").is_none());
+        assert!(loss_from_name("mean_squared_error(2)").is_none());
+    }
+
+    #[test]
+    fn fused_test() {
+        let x = array![[1.0, -2.0, 0.5]];
+        let expected = array![[0.0, 1.0, 0.0]];
+
+        let outputs = Activation::Softmax.activate(&x);
+        let (value, gradient) = CategoricalCrossEntropy
+            .fused(Activation::Softmax, &x, &outputs, &expected)
+            .unwrap();
+        assert!((value - CategoricalCrossEntropy.value(&outputs, &expected)).abs() < 1e-5);
+        assert_eq!(&outputs - &expected, gradient);
+
+        let outputs = Activation::Sigmoid.activate(&x);
+        let (value, gradient) = BinaryCrossEntropy
+            .fused(Activation::Sigmoid, &x, &outputs, &expected)
+            .unwrap();
+        assert!((value - BinaryCrossEntropy.value(&outputs, &expected)).abs() < 1e-5);
+        let unfused = Activation::Sigmoid.backpropagate(&x, &outputs, &BinaryCrossEntropy.gradient(&outputs, &expected));
+        for (a, b) in gradient.iter().zip(unfused.iter()) {
+            assert!((a - b).abs() < 1e-5);
+        }
+        assert!(BinaryCrossEntropy.fused(Activation::Relu, &x, &outputs, &expected).is_none());
+    }
+
+    #[test]
+    fn categorical_cross_entropy_test() {
+        let logits = array![[1.0, 2.0, 3.0]];
+        let expected = array![[0.0, 0.0, 1.0]];
+        assert!((categorical_cross_entropy(&logits, &expected) - 0.407_605_9).abs() < 1e-6);
+
+        // stays finite where a naive log(softmax) would not
+        let logits = array![[1000.0, 0.0], [0.0, 1000.0]];
+        let expected = array![[0.0, 1.0], [0.0, 1.0]];
+        assert_eq!(500.0, categorical_cross_entropy(&logits, &expected));
+    }
+}
@@ -1,5 +1,23 @@
 pub(crate) mod activation_functions;
+pub(crate) mod block;
+pub(crate) mod builder;
+pub(crate) mod callbacks;
+pub(crate) mod conv;
+pub(crate) mod dataset;
+pub(crate) mod dropout;
+pub(crate) mod error;
+pub(crate) mod gradient_check;
+pub(crate) mod gradients;
+pub(crate) mod idx;
 pub(crate) mod layer;
+pub(crate) mod loss_functions;
 pub(crate) mod network;
-pub mod neuron;
-pub mod weight_functions;
+pub(crate) mod neuron;
+pub(crate) mod normalization;
+pub(crate) mod optimizers;
+pub(crate) mod pooling;
+pub(crate) mod regularizers;
+pub(crate) mod schedules;
+pub(crate) mod serialization;
+pub(crate) mod trainer;
+pub(crate) mod weight_functions;
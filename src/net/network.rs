@@ -1,354 +1,805 @@
-use std::any::{Any, TypeId};
-use std::fmt;
-use std::fmt::Debug;
-
-use crate::net::layer::Layer;
-use itertools::Itertools;
-use ndarray::{Array0, Array1, ArrayBase};
-use crate::net::neuron::NeuronBase;
-
-use super::{
-    activation_functions,
-    layer::{HiddenLayer, InputLayer, OutputLayer},
-    neuron::{Bias, Hidden, Neuron},
-    weight_functions::xavier_init,
-};
-
-#[derive(Debug, Clone)]
-pub struct Network {
-    input_layer: InputLayer,
-    hidden_layer: Vec<HiddenLayer>,
-    output_layer: OutputLayer,
-}
-
-impl Network {
-    pub fn new(
-        input_layer: InputLayer,
-        hidden_layer: Vec<HiddenLayer>,
-        output_layer: OutputLayer,
-    ) -> Self {
-        Self {
-            input_layer,
-            hidden_layer,
-            output_layer,
-        }
-    }
-
-    pub fn forward_pass(&mut self) {
-        // for every value inside the input layer
-        // get each hidden layer and calculate
-
-        let input = &self.input_layer;
-        let inputs = input.values_as_arr();
-        // feed the first layer
-        let first_layer = self.hidden_layer.get_mut(0).unwrap();
-        for n in 0..first_layer.neurons.len() {
-            let mut neuron = first_layer.neurons.get_mut(n).unwrap();
-            if let Neuron::Hidden(h) = neuron {
-                h.input_value = inputs.dot(&h.weights);
-                h.output_value = (first_layer.activation_function)(h.input_value);
-            };
-        }
-
-        let hidden_layers = &mut self.hidden_layer;
-        if (hidden_layers).len() > 1 {
-            for n in (0..hidden_layers.len()-1) {
-                let prev_layer = hidden_layers.get(n).unwrap();
-                let values = prev_layer.values_as_arr();
-                let next_layer = &mut hidden_layers[n + 1];
-                for i in 0..next_layer.neurons.len() {
-                    let mut neuron = next_layer.neurons.get_mut(i).unwrap();
-                    if let Neuron::Hidden(h) = neuron {
-                        h.input_value = values.dot(&h.weights);
-                        h.output_value = (next_layer.activation_function)(h.input_value);
-                    }
-                }
-            }
-        }
-
-        let mut output_layer = &mut self.output_layer;
-        let last_hidden = hidden_layers.last().unwrap();
-        let layer_values = last_hidden.values_as_arr();
-        for n in 0..output_layer.outputs.len() {
-            let neuron = output_layer.outputs.get_mut(n).unwrap();
-
-            if let Neuron::Output(o) = neuron {
-                o.input_value = layer_values.dot(&o.weights);
-                o.output_value = (output_layer.activation_function)(o.input_value);
-            }
-        }
-    }
-
-
-
-
-    pub fn backward_pass(&mut self, expected: Vec<f32>) -> f32 {
-        //
-        let mut global_error = 0.0;
-        let learning_rate = 0.1;
-        let mut output_layer: &mut OutputLayer = &mut self.output_layer;
-        let mut activation_derivation = output_layer.activation_derivation;
-        let mut hidden_layer_values= self.hidden_layer.last().unwrap().values_as_arr();
-        let mut neuron_deltas: Vec<f32> = vec![0.0; hidden_layer_values.len()];
-
-        let mut outputs = output_layer.get_all_mut();
-        for n in 0..outputs.len() {
-
-            let neuron = outputs.get_mut(n).unwrap();
-            let diff = (neuron.get_output_value() - expected[n]) * activation_derivation(neuron.get_output_value());
-
-
-            //global_error += 0.5 * diff.powf(2.0);
-
-            let mut weights = neuron.get_mut_weights();
-
-            for lw in 0..weights.len() {
-                neuron_deltas[lw] += diff * weights[lw];
-
-                weights[lw] = weights[lw] - (learning_rate * diff * hidden_layer_values[lw]);
-            }
-        }
-
-
-        if (self.hidden_layer).len() > 1 {
-
-            for n in (1..self.hidden_layer.len()).rev() {
-                let next_layer_values = &mut self.hidden_layer[n -1].values_as_arr();
-                let mut hidden_layer = &mut self.hidden_layer[n];
-                activation_derivation = hidden_layer.activation_derivation;
-                let mut temp_deltas = vec![0.0; next_layer_values.len()];
-
-                for ln in 0..hidden_layer.len() {
-                    // calc new weights
-                    let mut ll_neuron: &mut Neuron = hidden_layer.get_mut(ln).unwrap();
-                    let mut ll_output = ll_neuron.get_output_value();
-                    let mut ll_weights = ll_neuron.get_mut_weights();
-
-                    neuron_deltas[ln] *= activation_derivation(ll_output);
-
-                    for lw in 0..ll_weights.len() {
-                        temp_deltas[lw] += ll_weights[lw] * neuron_deltas[lw];
-                        ll_weights[lw] = ll_weights[lw] - (learning_rate * neuron_deltas[lw] * next_layer_values[lw]);
-                    }
-                }
-                neuron_deltas = temp_deltas;
-            }
-        }
-
-
-
-        let mut input_layer = &mut self.input_layer;
-        let input_layer_values = input_layer.values_as_arr();
-        let mut current_layer = self.hidden_layer.first_mut().unwrap();
-
-        let activation_derivation = current_layer.activation_derivation;
-        for n in 0..current_layer.neurons.len() {
-            let mut neuron = current_layer.neurons.get_mut(n).unwrap();
-            neuron_deltas[n] *= activation_derivation(neuron.get_output_value());
-
-            if let Neuron::Hidden(h) = neuron {
-                let ll_weights = &mut h.weights;
-                for lw in 0..ll_weights.len() {
-                    ll_weights[lw] = ll_weights[lw] - (learning_rate * neuron_deltas[lw] * input_layer_values[lw]);
-                }
-            }
-
-
-
-        }
-        return global_error;
-    }
-
-
-    pub fn calc_total_error(&mut self, expected: Array1<f32>) -> f32 {
-        let mut error = 0.0;
-        for n in 0..self.output_layer.len() {
-            error += 0.5 * (self.output_layer.get(n).unwrap().get_output_value() - expected.get(n).unwrap()).powf(2.0);
-        }
-
-        return error;
-    }
-}
-
-impl fmt::Display for Network {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(
-            f,
-            "input: {:?}\nhidden: {:?}\noutput: {:?}",
-            self.input_layer, self.hidden_layer, self.output_layer
-        )
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use ndarray::array;
-    use rand::Rng;
-
-    use crate::net::{activation_functions, neuron::Output, weight_functions::xavier_init};
-
-    use super::*;
-
-    fn setup() -> Network{
-        let mut input_layer = InputLayer::new(2, false);
-        let mut hidden_a = HiddenLayer::new(
-            3, true,
-            activation_functions::sigmoid,
-            activation_functions::sigmoid_derivative,
-            xavier_init,
-            &input_layer,
-        );
-        let neurons = vec![
-            Neuron::Hidden(Hidden {
-                input_value: 0.0,
-                weights: array![-0.30, -0.41],
-                output_value: 0.0,
-            }),
-            Neuron::Hidden(Hidden {
-                input_value: 0.0,
-                weights: array![0.13, 0.31],
-                output_value: 0.0,
-            }),
-            Neuron::Bias(Bias {
-                input_value: 0.25,
-                output_value: 0.25,
-                weights: array![0.0],
-            })
-
-        ];
-        hidden_a.neurons = Array1::from_vec(neurons);
-
-        let neurons_b = vec![
-            Neuron::Hidden(Hidden {
-                input_value: 0.0,
-                weights: array![0.11, 0.21, 0.31],
-                output_value: 0.0,
-            }),
-            Neuron::Hidden(Hidden {
-                input_value: 0.0,
-                weights: array![-0.12, -0.08, -0.01],
-                output_value: 0.0,
-            }),
-            Neuron::Bias(Bias {
-                input_value: 0.25,
-                output_value: 0.25,
-                weights: array![0.0],
-            })
-
-        ];
-
-        let mut hidden_ab = HiddenLayer::new(
-            3,
-            true,
-            activation_functions::sigmoid,
-            activation_functions::sigmoid_derivative,
-            xavier_init,
-            &hidden_a,
-        );
-        hidden_ab.neurons = Array1::from_vec(neurons_b);
-        let mut output = OutputLayer::new(1, activation_functions::nop, activation_functions::nop_derivative, xavier_init, &hidden_ab);
-        let outputs = vec![Neuron::Output(Output {
-            input_value: 0.0,
-            weights: array![-0.013, 0.020, 0.4],
-            output_value: 0.0,
-        })];
-        output.outputs = Array1::from_vec(outputs);
-
-        Network::new(input_layer, vec![hidden_a, hidden_ab], output)
-    }
-
-    #[test]
-    fn network_feed_forward_test() {
-        let mut net = setup();
-        net.input_layer.set_inputs(vec![2.0, 3.0]);
-        net.forward_pass();
-
-        assert_eq!(1, 0);
-    }
-
-    #[test]
-    fn calc_error_test() {
-        let mut net = setup();
-        let values = array![0.191];
-        let expected = array![1.0];
-        assert_eq!(0.32724053, net.calc_total_error(expected));
-    }
-
-    #[test]
-    fn network_backward_pass_test() {
-        let mut net = setup();
-        net.input_layer.set_inputs(vec![0.5, 0.5]);
-        net.forward_pass();
-        println!("{:?}", &net);
-        net.backward_pass(vec![0.0]);
-
-
-        println!("{:?}", &net);
-        assert_eq!(1, 0);
-    }
-
-    #[test]
-    fn network_training_test() {
-        let mut net = setup();
-
-        let mut error = 1.0;
-        let data = array![[1.0, -0.5, -0.5],
-                                        [0.0, 0.5, -0.5],
-                                        [0.0, -0.5, 0.5],
-                                        [1.0, 0.5, 0.5]];
-
-        let iterations = 3000;
-        let mut rng = rand::thread_rng();
-
-        let mut error_vec: Vec<f32> = vec![];
-
-        println!("{:?}", &net);
-        for i in 0..iterations {
-            let index = rng.gen_range(0..data.shape()[0]-1);
-
-            net.input_layer.set_inputs(vec![data[[index,1]], data[[index,2]]]);
-            net.forward_pass();
-            error = net.backward_pass(vec![data[[index, 0]]]);
-
-            if i % 10 == 0 {
-                let mut total_error = 0.0;
-                for n in 0..(data.shape()[0]) {
-                    net.input_layer.set_inputs(vec![data[[n,1]], data[[n,2]]]);
-                    net.forward_pass();
-
-                    //total_error =net.output_layer.get(0).unwrap().get_output_value();
-                    total_error += net.output_layer.get(0).unwrap().get_output_value().abs() - data[[n, 0]].powf(2.0);
-                    //println!("{}: {} - {} {} {} {}", i, total_error, data[[n,1]], data[[n,2]], data[[n,0]], net.output_layer.get(0).unwrap().get_output_value());
-                }
-                total_error = (total_error*1.0/4.0).abs();
-
-                println!("{}: {}", i, total_error);
-                if total_error < 0.00001 {
-                    break;
-                }
-                //println!("{:?}", &net);
-            }
-
-        }
-
-        println!("{:?}", &net);
-        net.input_layer.set_inputs(vec![0.5, 0.5]);
-        net.forward_pass();
-        println!("{:?}", net.output_layer.get_all().get(0).unwrap().get_output_value().abs());
-
-        net.input_layer.set_inputs(vec![0.5, -0.5]);
-        net.forward_pass();
-        println!("{:?}", net.output_layer.get_all().get(0).unwrap().get_output_value().abs());
-
-        net.input_layer.set_inputs(vec![-0.5, 0.5]);
-        net.forward_pass();
-        println!("{:?}", net.output_layer.get_all().get(0).unwrap().get_output_value().abs());
-
-        net.input_layer.set_inputs(vec![-0.5, -0.5]);
-        net.forward_pass();
-        println!("{:?}", net.output_layer.get_all().get(0).unwrap().get_output_value().abs());
-
-
-
-    }
-}
+use std::fmt;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use ndarray::{Array1, Array2, ArrayViewMutD, Axis};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use super::block::{Block, BlockGradients};
+use super::error::Error;
+use super::gradients::Gradients;
+use super::layer::{HiddenLayer, InputLayer, Layer, OutputLayer};
+use super::loss_functions::{default_loss, Loss};
+use super::optimizers::Optimizer;
+
+#[derive(Debug, Clone)]
+pub struct Network {
+    input_layer: InputLayer,
+    layers: Vec<Block>,
+    output_layer: OutputLayer,
+    loss: Arc<dyn Loss>,
+    // the single source of randomness for training: shuffling, dropout masks and sampling
+    rng: ChaCha8Rng,
+    // dropout is only active in training mode
+    training: bool,
+}
+
+impl Network {
+    // the loss defaults to categorical cross entropy for a softmax output
+    // and to mean squared error otherwise, seed starts the rng of the network, which drives
+    // shuffling, dropout masks and sampling, the weights come from the rng given to the layers
+    pub fn new(
+        input_layer: InputLayer,
+        hidden_layer: Vec<HiddenLayer>,
+        output_layer: OutputLayer,
+        seed: u64,
+    ) -> Result<Self, Error> {
+        Self::with_blocks(input_layer, hidden_layer.into_iter().map(Block::Dense).collect(), output_layer, seed)
+    }
+
+    // like new with any kind of layer between the input and the output layer,
+    // the network starts in training mode
+    pub fn with_blocks(
+        input_layer: InputLayer,
+        layers: Vec<Block>,
+        output_layer: OutputLayer,
+        seed: u64,
+    ) -> Result<Self, Error> {
+        Self::with_rng(input_layer, layers, output_layer, ChaCha8Rng::seed_from_u64(seed))
+    }
+
+    pub(crate) fn with_rng(
+        input_layer: InputLayer,
+        layers: Vec<Block>,
+        output_layer: OutputLayer,
+        rng: ChaCha8Rng,
+    ) -> Result<Self, Error> {
+        let loss = default_loss(output_layer.activation);
+        let network = Self {
+            input_layer,
+            layers,
+            output_layer,
+            loss,
+            rng,
+            training: true,
+        };
+        network.validate()?;
+        Ok(network)
+    }
+
+    pub fn loss(&self) -> &dyn Loss {
+        self.loss.as_ref()
+    }
+
+    pub fn set_loss<L: Loss + 'static>(&mut self, loss: L) {
+        self.loss = Arc::new(loss);
+    }
+
+    pub(crate) fn set_shared_loss(&mut self, loss: Arc<dyn Loss>) {
+        self.loss = loss;
+    }
+
+    // restart the rng of the network from seed
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+    }
+
+    pub fn rng(&mut self) -> &mut ChaCha8Rng {
+        &mut self.rng
+    }
+
+    pub(crate) fn rng_state(&self) -> &ChaCha8Rng {
+        &self.rng
+    }
+
+    pub(crate) fn set_rng(&mut self, rng: ChaCha8Rng) {
+        self.rng = rng;
+    }
+
+    // switch to training mode, the forward passes drop values in the dropout layers
+    // and batch normalisation uses and tracks the statistics of every batch
+    pub fn train(&mut self) {
+        self.training = true;
+    }
+
+    // switch to inference mode, the forward passes are deterministic
+    pub fn eval(&mut self) {
+        self.training = false;
+    }
+
+    pub fn is_training(&self) -> bool {
+        self.training
+    }
+
+    // the trainable parameters of every layer after the input layer, output layer last,
+    // in the order of the arrays of the layers of Gradients
+    pub(crate) fn params_mut(&mut self) -> Vec<Vec<ArrayViewMutD<'_, f32>>> {
+        let mut params: Vec<_> = self.layers.iter_mut().map(Block::params_mut).collect();
+        params.push(self.output_layer.params_mut());
+        params
+    }
+
+    pub fn input_layer(&self) -> &InputLayer {
+        &self.input_layer
+    }
+
+    // every layer between the input layer and the output layer
+    pub fn blocks(&self) -> &[Block] {
+        &self.layers
+    }
+
+    pub(crate) fn blocks_mut(&mut self) -> &mut [Block] {
+        &mut self.layers
+    }
+
+    // the dense layers between the input layer and the output layer
+    pub fn hidden_layers(&self) -> Vec<&HiddenLayer> {
+        self.layers.iter().filter_map(Block::as_dense).collect()
+    }
+
+    pub fn output_layer(&self) -> &OutputLayer {
+        &self.output_layer
+    }
+
+    pub(crate) fn output_layer_mut(&mut self) -> &mut OutputLayer {
+        &mut self.output_layer
+    }
+
+    // check that every weight matrix matches the width of the layer feeding it
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut prev_len = self.input_layer.len();
+        if prev_len == 0 {
+            return Err(Error::EmptyLayer { layer: 0 });
+        }
+
+        for (n, block) in self.layers.iter().enumerate() {
+            let layer = n + 1;
+            let invalid = |message: String| Err(Error::InvalidLayer { layer, message });
+            match block {
+                Block::Dense(dense) => check_dense(&dense.weights, &dense.biases, prev_len, layer)?,
+                Block::Dropout(dropout) if !(0.0..1.0).contains(&dropout.rate) => {
+                    return invalid(format!("dropout rate {} is outside [0, 1)", dropout.rate));
+                }
+                Block::BatchNorm(norm) => {
+                    if !(0.0..1.0).contains(&norm.momentum) {
+                        return invalid(format!("momentum {} is outside [0, 1)", norm.momentum));
+                    }
+                    let params = [&norm.shift, &norm.running_mean, &norm.running_variance];
+                    check_norm(norm.epsilon, &norm.scale, &params, layer)?;
+                }
+                Block::LayerNorm(norm) => check_norm(norm.epsilon, &norm.scale, &[&norm.shift], layer)?,
+                Block::RmsNorm(norm) => check_norm(norm.epsilon, &norm.scale, &[], layer)?,
+                Block::Conv2D(conv) => {
+                    let (channels, (kh, kw)) = (conv.input_shape.0, conv.kernel);
+                    check_dense(&conv.weights, &conv.biases, channels * kh * kw, layer)?;
+                    if conv.output_shape.1 * conv.output_shape.2 == 0 {
+                        return invalid(format!(
+                            "kernel {:?} with stride {:?} and padding {} does not fit the input {:?}",
+                            conv.kernel, conv.stride, conv.padding, conv.input_shape
+                        ));
+                    }
+                }
+                Block::Conv1D(conv) => {
+                    if conv.kernel == 0 {
+                        return invalid("kernel 0 is not positive".to_string());
+                    }
+                    check_dense(&conv.weights, &conv.biases, conv.input_shape.0 * conv.kernel, layer)?;
+                    if conv.dilation == 0 {
+                        return invalid("dilation 0 is not positive".to_string());
+                    }
+                    if conv.output_shape.1 == 0 {
+                        return invalid(format!(
+                            "kernel {} with stride {}, dilation {} and padding {} does not fit the input {:?}",
+                            conv.kernel, conv.stride, conv.dilation, conv.padding, conv.input_shape
+                        ));
+                    }
+                }
+                Block::GlobalAveragePool1D(pool) if pool.input_shape.1 == 0 => {
+                    return invalid("global pooling of empty sequences".to_string());
+                }
+                Block::GlobalMaxPool1D(pool) if pool.input_shape.1 == 0 => {
+                    return invalid("global pooling of empty sequences".to_string());
+                }
+                Block::MaxPool2D(pool) if pool.len() == 0 => {
+                    return invalid(format!("pool {} does not fit the input", pool.window));
+                }
+                Block::AvgPool2D(pool) if pool.len() == 0 => {
+                    return invalid(format!("pool {} does not fit the input", pool.window));
+                }
+                _ => {}
+            }
+            if block.input_len() != prev_len {
+                return Err(Error::ShapeMismatch {
+                    layer,
+                    expected: prev_len,
+                    found: block.input_len(),
+                });
+            }
+            prev_len = block.len();
+        }
+        let output = &self.output_layer;
+        check_dense(&output.weights, &output.biases, prev_len, self.output_index())
+    }
+
+    pub fn set_inputs(&mut self, input_values: Vec<f32>) -> Result<(), Error> {
+        self.input_layer.set_inputs(input_values)
+    }
+
+    pub fn forward_pass(&mut self) -> Result<(), Error> {
+        self.propagate()
+    }
+
+    // run a forward pass over every row of inputs and return one row of outputs per sample
+    pub fn forward_batch(&mut self, inputs: &Array2<f32>) -> Result<Array2<f32>, Error> {
+        self.input_layer.set_batch(inputs)?;
+        self.propagate()?;
+        Ok(self.output_layer.output_values.clone())
+    }
+
+    fn propagate(&mut self) -> Result<(), Error> {
+        // feed the values of each layer into the next one
+        let mut values = self.input_layer.batch_values();
+        for layer in self.layers.iter_mut() {
+            layer.forward(&values, self.training, &mut self.rng);
+            values = layer.batch_values();
+        }
+        self.output_layer.forward(&values);
+
+        if !self.output_layer.output_values.iter().all(|v| v.is_finite()) {
+            return Err(Error::NonFinite {
+                layer: self.output_index(),
+            });
+        }
+        Ok(())
+    }
+
+    fn output_index(&self) -> usize {
+        self.layers.len() + 1
+    }
+
+    pub fn backward_pass(&mut self, expected: Vec<f32>, optimizer: &mut dyn Optimizer) -> Result<f32, Error> {
+        self.backward_batch(&Array1::from_vec(expected).insert_axis(Axis(0)), optimizer)
+    }
+
+    // backpropagate the last forward pass and let the optimizer apply a single update
+    // with the gradients averaged over all rows of the batch,
+    // returns the loss of the batch before the update
+    pub fn backward_batch(&mut self, expected: &Array2<f32>, optimizer: &mut dyn Optimizer) -> Result<f32, Error> {
+        check_expected(&self.output_layer.output_values, expected, self.output_index())?;
+        let gradients = self.backprop(expected);
+        self.apply_gradients(&gradients, optimizer)?;
+        Ok(gradients.loss)
+    }
+
+    // gradients of the loss of input against target averaged over the batch,
+    // the forward pass runs on a copy so the network, including the values of the last forward pass,
+    // its rng and the running statistics of batch normalisation, is untouched,
+    // in training mode repeated calls therefore draw the same dropout masks
+    pub fn compute_gradients(&self, input: &Array2<f32>, target: &Array2<f32>) -> Result<Gradients, Error> {
+        let mut probe = self.clone();
+        probe.forward_batch(input)?;
+        check_expected(&probe.output_layer.output_values, target, self.output_index())?;
+        Ok(probe.backprop(target))
+    }
+
+    // a single optimizer step with gradients of a network with the same architecture
+    pub fn apply_gradients(&mut self, gradients: &Gradients, optimizer: &mut dyn Optimizer) -> Result<(), Error> {
+        let layers = &gradients.layers;
+        if layers.len() != self.layers.len() + 1 {
+            return Err(Error::ShapeMismatch {
+                layer: self.output_index(),
+                expected: self.layers.len() + 1,
+                found: layers.len(),
+            });
+        }
+        // check the kinds before the shapes, a norm layer and a dense layer can have the same shapes
+        for (n, (layer, gradients)) in self.layers.iter().zip(layers.iter()).enumerate() {
+            if !layer.accepts(gradients) {
+                return Err(Error::InvalidLayer {
+                    layer: n + 1,
+                    message: format!("{} gradients do not match a {} layer", gradients.kind(), layer.kind()),
+                });
+            }
+        }
+        let output_index = self.layers.len();
+        let BlockGradients::Dense(output_gradients) = &layers[output_index] else {
+            return Err(Error::InvalidLayer {
+                layer: self.output_index(),
+                message: format!("{} gradients do not match the output layer", layers[output_index].kind()),
+            });
+        };
+        for (n, (params, gradients)) in self.params_mut().iter().zip(layers.iter()).enumerate() {
+            let arrays = gradients.arrays();
+            let same = params.len() == arrays.len() && params.iter().zip(arrays.iter()).all(|(p, g)| p.shape() == g.shape());
+            if !same {
+                return Err(Error::ShapeMismatch {
+                    layer: n + 1,
+                    expected: params.iter().map(|p| p.len()).sum(),
+                    found: arrays.iter().map(|g| g.len()).sum(),
+                });
+            }
+        }
+
+        optimizer.begin_step();
+        for (n, (layer, gradients)) in self.layers.iter_mut().zip(layers.iter()).enumerate() {
+            layer.apply(gradients, optimizer, n)?;
+        }
+        self.output_layer.apply(output_gradients, optimizer, output_index);
+        Ok(())
+    }
+
+    // gradients of every layer for the values cached by the last forward pass, output layer last
+    fn backprop(&self, expected: &Array2<f32>) -> Gradients {
+        let output = &self.output_layer;
+        let (loss, neuron_deltas) = self.output_deltas(&output.input_values, &output.output_values, expected);
+        let mut layers = Vec::with_capacity(self.layers.len() + 1);
+
+        let prev_values = |n: usize| match n {
+            0 => self.input_layer.batch_values(),
+            _ => self.layers[n - 1].batch_values(),
+        };
+        layers.push(BlockGradients::Dense(
+            output.gradients(&prev_values(self.layers.len()), &neuron_deltas),
+        ));
+        let mut propagated = output.propagate(&neuron_deltas);
+
+        for n in (0..self.layers.len()).rev() {
+            let (gradients, deltas) = self.layers[n].backward(&prev_values(n), &propagated);
+            layers.push(gradients);
+            propagated = deltas;
+        }
+
+        layers.reverse();
+        Gradients {
+            loss: loss + self.penalty(),
+            layers,
+        }
+    }
+
+    // loss of the outputs and its gradient with respect to the output pre-activations x
+    fn output_deltas(&self, x: &Array2<f32>, outputs: &Array2<f32>, expected: &Array2<f32>) -> (f32, Array2<f32>) {
+        let activation = self.output_layer.activation;
+        match self.loss.fused(activation, x, outputs, expected) {
+            Some(fused) => fused,
+            None => (
+                self.loss.value(outputs, expected),
+                activation.backpropagate(x, outputs, &self.loss.gradient(outputs, expected)),
+            ),
+        }
+    }
+
+    // loss of the last forward pass
+    pub fn calc_total_error(&self, expected: Array1<f32>) -> Result<f32, Error> {
+        self.calc_batch_error(&expected.insert_axis(Axis(0)))
+    }
+
+    // includes the l1 and l2 penalties of the layers
+    pub fn calc_batch_error(&self, expected: &Array2<f32>) -> Result<f32, Error> {
+        let output_layer = &self.output_layer;
+        check_expected(&output_layer.output_values, expected, self.output_index())?;
+        let (loss, _) = self.output_deltas(&output_layer.input_values, &output_layer.output_values, expected);
+        Ok(loss + self.penalty())
+    }
+
+    // sum of the l1 and l2 penalties of all layers
+    pub fn penalty(&self) -> f32 {
+        let hidden: f32 = self.layers.iter().map(Block::penalty).sum();
+        let output = &self.output_layer;
+        hidden + output.regularizer.penalty(&output.weights, &output.biases)
+    }
+}
+
+// the weights of a dense layer at index layer must match the width prev_len of the layer before it
+fn check_dense(weights: &Array2<f32>, biases: &Array1<f32>, prev_len: usize, layer: usize) -> Result<(), Error> {
+    if biases.is_empty() {
+        return Err(Error::EmptyLayer { layer });
+    }
+    if weights.nrows() != prev_len {
+        return Err(Error::ShapeMismatch {
+            layer,
+            expected: prev_len,
+            found: weights.nrows(),
+        });
+    }
+    if weights.ncols() != biases.len() {
+        return Err(Error::ShapeMismatch {
+            layer,
+            expected: biases.len(),
+            found: weights.ncols(),
+        });
+    }
+    Ok(())
+}
+
+// epsilon must be positive and every parameter of a normalisation layer as long as its scale
+fn check_norm(epsilon: f32, scale: &Array1<f32>, params: &[&Array1<f32>], layer: usize) -> Result<(), Error> {
+    if epsilon <= 0.0 || epsilon.is_nan() {
+        return Err(Error::InvalidLayer {
+            layer,
+            message: format!("epsilon {} is not positive", epsilon),
+        });
+    }
+    if let Some(param) = params.iter().find(|p| p.len() != scale.len()) {
+        return Err(Error::ShapeMismatch {
+            layer,
+            expected: scale.len(),
+            found: param.len(),
+        });
+    }
+    Ok(())
+}
+
+// the expected values must match the outputs of the output layer at index layer
+fn check_expected(outputs: &Array2<f32>, expected: &Array2<f32>, layer: usize) -> Result<(), Error> {
+    if expected.ncols() != outputs.ncols() {
+        return Err(Error::ShapeMismatch {
+            layer,
+            expected: outputs.ncols(),
+            found: expected.ncols(),
+        });
+    }
+    if expected.nrows() != outputs.nrows() {
+        return Err(Error::ShapeMismatch {
+            layer,
+            expected: outputs.nrows(),
+            found: expected.nrows(),
+        });
+    }
+    if !expected.iter().all(|v| v.is_finite()) {
+        return Err(Error::NonFinite { layer });
+    }
+    Ok(())
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "input: {:?}\nhidden: {:?}\noutput: {:?}",
+            self.input_layer, self.layers, self.output_layer
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{array, s};
+
+    use crate::net::activation_functions::Activation;
+    use crate::net::loss_functions::MeanAbsoluteError;
+    use crate::net::dataset::Dataset;
+    use crate::net::optimizers::{Adam, Sgd};
+    use crate::net::trainer::{evaluate, TrainConfig, Trainer};
+    use rand::rngs::StdRng;
+    use rand::RngCore;
+
+    use super::*;
+
+    fn setup() -> Network {
+        let input_layer = InputLayer::new(2);
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut hidden_a = HiddenLayer::new(
+            2,
+            false,
+            Activation::Sigmoid,
+            &mut rng,
+            &input_layer,
+        );
+        hidden_a.weights = array![[-0.30, 0.13], [-0.41, 0.31]];
+
+        let mut hidden_ab = HiddenLayer::new(
+            2,
+            true,
+            Activation::Sigmoid,
+            &mut rng,
+            &hidden_a,
+        );
+        hidden_ab.weights = array![[0.11, -0.12], [0.21, -0.08]];
+        hidden_ab.biases = array![0.0775, -0.0025];
+
+        let mut output = OutputLayer::new(
+            1,
+            true,
+            Activation::Identity,
+            &mut rng,
+            &hidden_ab,
+        );
+        output.weights = array![[-0.013], [0.020]];
+        output.biases = array![0.1];
+
+        Network::new(input_layer, vec![hidden_a, hidden_ab], output, 0).unwrap()
+    }
+
+    #[test]
+    fn seed_test() {
+        // networks assembled by hand with the same seed draw the same numbers
+        let (mut a, mut b) = (setup(), setup());
+        assert_eq!(a.rng().next_u64(), b.rng().next_u64());
+        let input_layer = InputLayer::new(2);
+        let output = OutputLayer::new(1, true, Activation::Identity, &mut StdRng::seed_from_u64(0), &input_layer);
+        let mut c = Network::new(input_layer, vec![], output, 1).unwrap();
+        assert_ne!(a.rng().next_u64(), c.rng().next_u64());
+    }
+
+    #[test]
+    fn validate_test() {
+        let mut net = setup();
+        assert_eq!(Ok(()), net.validate());
+
+        net.layers[1].as_dense_mut().unwrap().weights = array![[0.11, -0.12], [0.21, -0.08], [0.0, 0.0]];
+        assert_eq!(
+            Err(Error::ShapeMismatch { layer: 2, expected: 2, found: 3 }),
+            net.validate()
+        );
+    }
+
+    #[test]
+    fn network_error_test() {
+        let mut net = setup();
+        assert_eq!(
+            Err(Error::ShapeMismatch { layer: 0, expected: 2, found: 1 }),
+            net.set_inputs(vec![1.0])
+        );
+        net.set_inputs(vec![1.0, 1.0]).unwrap();
+        net.forward_pass().unwrap();
+        assert_eq!(
+            Err(Error::ShapeMismatch { layer: 3, expected: 1, found: 2 }),
+            net.backward_pass(vec![1.0, 0.0], &mut Sgd::new(0.1))
+        );
+        assert_eq!(
+            Err(Error::NonFinite { layer: 3 }),
+            net.backward_pass(vec![f32::INFINITY], &mut Sgd::new(0.1))
+        );
+        assert_eq!(Err(Error::EmptyBatch), net.forward_batch(&Array2::zeros((0, 2))));
+
+        net.output_layer.weights[[0, 0]] = f32::INFINITY;
+        assert_eq!(Err(Error::NonFinite { layer: 3 }), net.forward_pass());
+    }
+
+    #[test]
+    fn network_feed_forward_test() {
+        let mut net = setup();
+        net.set_inputs(vec![2.0, 3.0]).unwrap();
+        net.forward_pass().unwrap();
+
+        let hidden = net.layers[1].values_as_arr();
+        assert!((hidden[0] - 0.563_092_4).abs() < 1e-6);
+        assert!((hidden[1] - 0.479_903_9).abs() < 1e-6);
+        assert!((net.output_layer.values_as_arr()[0] - 0.102_277_88).abs() < 1e-6);
+    }
+
+    #[test]
+    fn calc_error_test() {
+        let mut net = setup();
+        net.output_layer.output_values = array![[0.191]];
+        assert!((net.calc_total_error(array![1.0]).unwrap() - 0.654_481).abs() < 1e-6);
+
+        net.set_loss(MeanAbsoluteError);
+        assert!((net.calc_total_error(array![1.0]).unwrap() - 0.809).abs() < 1e-6);
+    }
+
+    #[test]
+    fn network_backward_pass_test() {
+        let mut net = setup();
+        net.set_inputs(vec![0.5, 0.5]).unwrap();
+        net.forward_pass().unwrap();
+        let before = net.calc_total_error(array![0.0]).unwrap();
+        let weights = net.hidden_layers()[0].weights.clone();
+
+        assert_eq!(Ok(before), net.backward_pass(vec![0.0], &mut Sgd::new(0.1)));
+        assert_ne!(weights, net.hidden_layers()[0].weights);
+
+        net.forward_pass().unwrap();
+        assert!(net.calc_total_error(array![0.0]).unwrap() < before);
+    }
+
+    #[test]
+    fn network_batch_test() {
+        let mut net = setup();
+        let inputs = array![[2.0, 3.0], [0.5, 0.5]];
+        let outputs = net.forward_batch(&inputs).unwrap();
+        assert_eq!((2, 1), outputs.dim());
+
+        let mut single = setup();
+        single.set_inputs(vec![2.0, 3.0]).unwrap();
+        single.forward_pass().unwrap();
+        assert_eq!(single.output_layer.values_as_arr()[0], outputs[[0, 0]]);
+
+        // a batch of identical rows updates like a single sample
+        let mut batch = setup();
+        batch.forward_batch(&array![[0.5, 0.5], [0.5, 0.5]]).unwrap();
+        batch.backward_batch(&array![[0.0], [0.0]], &mut Sgd::new(0.1)).unwrap();
+        let mut single = setup();
+        single.set_inputs(vec![0.5, 0.5]).unwrap();
+        single.forward_pass().unwrap();
+        single.backward_pass(vec![0.0], &mut Sgd::new(0.1)).unwrap();
+        for (b, s) in batch.hidden_layers()[0].weights.iter().zip(single.hidden_layers()[0].weights.iter()) {
+            assert!((b - s).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn compute_gradients_test() {
+        let inputs = array![[2.0, 3.0], [0.5, 0.5], [-1.0, 0.5], [0.0, -2.0]];
+        let expected = array![[1.0], [0.0], [0.5], [-1.0]];
+
+        let mut net = setup();
+        net.set_inputs(vec![1.0, 1.0]).unwrap();
+        net.forward_pass().unwrap();
+        let cached = net.output_layer.output_values.clone();
+        let gradients = net.compute_gradients(&inputs, &expected).unwrap();
+        assert_eq!(cached, net.output_layer.output_values);
+
+        // applying the gradients matches a backward pass over the same batch
+        let mut backward = setup();
+        backward.forward_batch(&inputs).unwrap();
+        let loss = backward.backward_batch(&expected, &mut Sgd::new(0.1)).unwrap();
+        assert_eq!(loss, gradients.loss);
+        net.apply_gradients(&gradients, &mut Sgd::new(0.1)).unwrap();
+        assert_eq!(backward.hidden_layers()[0].weights, net.hidden_layers()[0].weights);
+        assert_eq!(backward.output_layer.biases, net.output_layer.biases);
+
+        // the mean over two halves equals the gradients of the whole batch
+        let net = setup();
+        let halves = [
+            net.compute_gradients(&inputs.slice(s![..2, ..]).to_owned(), &expected.slice(s![..2, ..]).to_owned()),
+            net.compute_gradients(&inputs.slice(s![2.., ..]).to_owned(), &expected.slice(s![2.., ..]).to_owned()),
+        ];
+        let halves: Vec<Gradients> = halves.into_iter().map(Result::unwrap).collect();
+        let mean = Gradients::mean(&halves).unwrap().unwrap();
+        let whole = net.compute_gradients(&inputs, &expected).unwrap();
+        for (a, b) in mean.layers.iter().zip(whole.layers.iter()) {
+            for (a, b) in a.arrays().iter().zip(b.arrays().iter()) {
+                assert!((a - b).iter().all(|d| d.abs() < 1e-6));
+            }
+        }
+        assert!((mean.loss - whole.loss).abs() < 1e-6);
+
+        let mut clipped = whole.clone();
+        assert_eq!(whole.norm(), clipped.clip_norm(whole.norm() / 2.0));
+        assert!((clipped.norm() - whole.norm() / 2.0).abs() < 1e-6);
+
+        let mut truncated = whole.clone();
+        truncated.layers.pop();
+        assert_eq!(
+            Err(Error::ShapeMismatch {
+                layer: 3,
+                expected: 3,
+                found: 2
+            }),
+            setup().apply_gradients(&truncated, &mut Sgd::new(0.1))
+        );
+
+        // gradients of the right shapes but the wrong kind, for a hidden layer and the output layer
+        for n in 0..whole.layers.len() {
+            let mut mismatched = whole.clone();
+            let BlockGradients::Dense(dense) = mismatched.layers[n].clone() else { unreachable!() };
+            mismatched.layers[n] = BlockGradients::Conv(dense);
+            let mut net = setup();
+            let before = net.output_layer.weights.clone();
+            assert!(matches!(
+                net.apply_gradients(&mismatched, &mut Sgd::new(0.1)),
+                Err(Error::InvalidLayer { layer, .. }) if layer == n + 1
+            ));
+            assert_eq!(before, net.output_layer.weights);
+        }
+    }
+
+    #[test]
+    fn dropout_test() {
+        let mut net = crate::net::builder::NetworkBuilder::new(2)
+            .seed(5)
+            .dense(16, Activation::Relu)
+            .dropout(0.5)
+            .dense(1, Activation::Identity)
+            .build()
+            .unwrap();
+        let inputs = array![[0.5, -1.0]];
+        assert!(net.is_training());
+        assert_ne!(net.forward_batch(&inputs).unwrap(), net.forward_batch(&inputs).unwrap());
+
+        net.eval();
+        let outputs = net.forward_batch(&inputs).unwrap();
+        assert_eq!(outputs, net.forward_batch(&inputs).unwrap());
+        let Block::Dense(hidden) = &net.blocks()[0] else { unreachable!() };
+        let expected = hidden.output_values.dot(&net.output_layer.weights) + &net.output_layer.biases;
+        assert!((&outputs - &expected).iter().all(|d| d.abs() < 1e-6));
+
+        // the mask of the forward pass decides which hidden neurons get gradients
+        net.train();
+        let gradients = net.compute_gradients(&inputs, &array![[1.0]]).unwrap();
+        let mut probe = net.clone();
+        probe.forward_batch(&inputs).unwrap();
+        let Block::Dropout(dropout) = &probe.blocks()[1] else { unreachable!() };
+        let mask = dropout.mask.as_ref().unwrap();
+        assert!(mask.iter().any(|m| *m == 0.0) && mask.iter().any(|m| *m == 2.0));
+        let BlockGradients::Dense(hidden) = &gradients.layers[0] else { unreachable!() };
+        for (column, m) in hidden.weights.columns().into_iter().zip(mask.iter()) {
+            if *m == 0.0 {
+                assert!(column.iter().all(|g| *g == 0.0));
+            }
+        }
+        assert_eq!(BlockGradients::Empty, gradients.layers[1]);
+    }
+
+    #[test]
+    fn layer_norm_test() {
+        let mut net = crate::net::builder::NetworkBuilder::new(2)
+            .seed(6)
+            .dense(8, Activation::Identity)
+            .layer_norm()
+            .activation(Activation::Relu)
+            .dense(4, Activation::Tanh)
+            .rms_norm()
+            .dense(1, Activation::Identity)
+            .build()
+            .unwrap();
+        let inputs = array![[2.0, 3.0], [0.5, -0.5]];
+        let outputs = net.forward_batch(&inputs).unwrap();
+
+        // every sample is normalised on its own, so single forward passes give the same outputs
+        for (row, output) in inputs.rows().into_iter().zip(outputs.iter()) {
+            net.set_inputs(row.to_vec()).unwrap();
+            net.forward_pass().unwrap();
+            assert!((net.output_layer.values_as_arr()[0] - output).abs() < 1e-6);
+        }
+        net.backward_pass(vec![1.0], &mut Sgd::new(0.1)).unwrap();
+        let Block::LayerNorm(norm) = &net.blocks()[1] else { unreachable!() };
+        assert!(norm.scale.iter().any(|s| *s != 1.0));
+    }
+
+    #[test]
+    fn softmax_training_test() {
+        let input_layer = InputLayer::new(2);
+        let output = OutputLayer::new(3, true, Activation::Softmax, &mut StdRng::seed_from_u64(0), &input_layer);
+        let mut net = Network::new(input_layer, vec![], output, 0).unwrap();
+
+        let inputs = array![[1.0, 0.0], [0.0, 1.0], [-1.0, -1.0]];
+        let expected = array![[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+        net.set_inputs(vec![1.0, 0.0]).unwrap();
+        net.forward_pass().unwrap();
+        let before = net.calc_total_error(array![1.0, 0.0, 0.0]).unwrap();
+        let mut optimizer = Adam::new(0.05);
+        for _ in 0..200 {
+            net.forward_batch(&inputs).unwrap();
+            net.backward_batch(&expected, &mut optimizer).unwrap();
+        }
+        let outputs = net.forward_batch(&inputs).unwrap();
+        for (row, label) in outputs.rows().into_iter().zip(0..3) {
+            assert!((row.sum() - 1.0).abs() < 1e-5);
+            assert!(row[label] > 0.5);
+        }
+
+        net.set_inputs(vec![1.0, 0.0]).unwrap();
+        net.forward_pass().unwrap();
+        assert!(net.calc_total_error(array![1.0, 0.0, 0.0]).unwrap() < before);
+    }
+
+    #[test]
+    fn network_training_test() {
+        let mut net = setup();
+
+        let data = array![[1.0, -0.5, -0.5],
+                                        [0.0, 0.5, -0.5],
+                                        [0.0, -0.5, 0.5],
+                                        [1.0, 0.5, 0.5]];
+        let dataset = Dataset::new(
+            data.slice(s![.., 1..]).to_owned(),
+            data.slice(s![.., ..1]).to_owned(),
+        )
+        .unwrap();
+
+        let before = evaluate(&mut net, &dataset).unwrap();
+        let config = TrainConfig {
+            epochs: 750,
+            batch_size: 1,
+            ..TrainConfig::default()
+        };
+        let history = Trainer::new(Sgd::new(0.1)).fit(&mut net, &dataset, config).unwrap();
+
+        assert_eq!(750, history.epochs());
+        assert!(evaluate(&mut net, &dataset).unwrap() < before);
+    }
+}
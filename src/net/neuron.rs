@@ -1,6 +1,7 @@
 use ndarray::prelude::*;
 use std::fmt;
-use rand::distributions::WeightedError;
+
+use super::error::Error;
 
 #[derive(Clone, Debug)]
 pub enum Neuron {
@@ -10,6 +11,17 @@ pub enum Neuron {
     Output(Output),
 }
 
+impl Neuron {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Neuron::Input(_) => "input",
+            Neuron::Bias(_) => "bias",
+            Neuron::Hidden(_) => "hidden",
+            Neuron::Output(_) => "output",
+        }
+    }
+}
+
 pub trait NeuronBase {
     fn get_input_value(&self) -> f32;
     fn set_input_value(&mut self, input_value: f32);
@@ -171,34 +183,43 @@ impl NeuronBase for Output {
 }
 
 impl TryFrom<Neuron> for Input {
-    type Error = Neuron;
+    type Error = Error;
 
     fn try_from(other: Neuron) -> Result<Self, Self::Error> {
         match other {
             Neuron::Input(c) => Ok(c),
-            a => Err(a),
+            a => Err(Error::WrongNeuronKind {
+                expected: "input",
+                found: a.kind(),
+            }),
         }
     }
 }
 
 impl TryFrom<Neuron> for Hidden {
-    type Error = Neuron;
+    type Error = Error;
 
     fn try_from(other: Neuron) -> Result<Self, Self::Error> {
         match other {
             Neuron::Hidden(c) => Ok(c),
-            a => Err(a),
+            a => Err(Error::WrongNeuronKind {
+                expected: "hidden",
+                found: a.kind(),
+            }),
         }
     }
 }
 
 impl TryFrom<Neuron> for Output {
-    type Error = Neuron;
+    type Error = Error;
 
     fn try_from(other: Neuron) -> Result<Self, Self::Error> {
         match other {
             Neuron::Output(c) => Ok(c),
-            a => Err(a),
+            a => Err(Error::WrongNeuronKind {
+                expected: "output",
+                found: a.kind(),
+            }),
         }
     }
 }
@@ -208,7 +229,7 @@ impl Input {
         Input {
             input_value: 0.0,
             output_value: 0.0,
-            weights: array![0.0],
+            weights,
         }
     }
 }
@@ -277,6 +298,12 @@ impl Bias {
     }
 }
 
+impl Default for Bias {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NeuronBase for Bias {
     fn get_input_value(&self) -> f32 {
         self.input_value
@@ -0,0 +1,418 @@
+use std::fmt;
+
+use ndarray::prelude::*;
+
+use super::activation_functions::Activation;
+use super::layer::{weightless_neuron, Layer};
+use super::neuron::Neuron;
+use super::optimizers::Optimizer;
+
+// gradients of the scale and shift of a normalisation layer averaged over the batch
+#[derive(Debug, Clone, PartialEq)]
+pub struct NormGradients {
+    pub scale: Array1<f32>,
+    // empty for a layer without shift
+    pub shift: Array1<f32>,
+}
+
+impl NormGradients {
+    // the gradients of the trainable parameters, the scale followed by the shift if there is one
+    pub fn arrays(&self) -> Vec<ArrayViewD<'_, f32>> {
+        let mut arrays = vec![self.scale.view().into_dyn()];
+        if !self.shift.is_empty() {
+            arrays.push(self.shift.view().into_dyn());
+        }
+        arrays
+    }
+
+    pub fn arrays_mut(&mut self) -> Vec<ArrayViewMutD<'_, f32>> {
+        let mut arrays = vec![self.scale.view_mut().into_dyn()];
+        if !self.shift.is_empty() {
+            arrays.push(self.shift.view_mut().into_dyn());
+        }
+        arrays
+    }
+}
+
+// normalises every value over the batch to zero mean and unit variance and then applies the learned
+// scale and shift, in training mode with the statistics of the batch, which also move the running
+// statistics, and in inference mode with the running statistics
+#[derive(Debug, Clone)]
+pub struct BatchNorm {
+    pub scale: Array1<f32>,
+    pub shift: Array1<f32>,
+    pub running_mean: Array1<f32>,
+    // the biased variance of the batches
+    pub running_variance: Array1<f32>,
+    // weight of the running statistics in every update, in [0, 1)
+    pub momentum: f32,
+    // added to the variance before taking the square root
+    pub epsilon: f32,
+    // (batch_size, layer_size) values of the last forward pass
+    pub input_values: Array2<f32>,
+    pub output_values: Array2<f32>,
+    // normalised inputs and 1 / sqrt(variance + epsilon) of the last forward pass
+    normalized: Array2<f32>,
+    inverse_std: Array1<f32>,
+    // whether the last forward pass normalised with the statistics of its batch
+    batch_statistics: bool,
+}
+
+impl BatchNorm {
+    // keeps the size of prev_layer, starts as the identity with momentum 0.9 and epsilon 1e-5
+    pub fn new(prev_layer: &dyn Layer) -> Self {
+        let layer_size = prev_layer.len();
+        Self {
+            scale: Array1::ones(layer_size),
+            shift: Array1::zeros(layer_size),
+            running_mean: Array1::zeros(layer_size),
+            running_variance: Array1::ones(layer_size),
+            momentum: 0.9,
+            epsilon: 1e-5,
+            input_values: Array2::zeros((1, layer_size)),
+            output_values: Array2::zeros((1, layer_size)),
+            normalized: Array2::zeros((1, layer_size)),
+            inverse_std: Array1::ones(layer_size),
+            batch_statistics: false,
+        }
+    }
+
+    pub fn forward(&mut self, inputs: &ArrayView2<f32>, training: bool) {
+        let (mean, variance) = match training {
+            true => {
+                let mean = inputs.mean_axis(Axis(0)).unwrap();
+                let variance = inputs.var_axis(Axis(0), 0.0);
+                let momentum = self.momentum;
+                self.running_mean = &self.running_mean * momentum + &mean * (1.0 - momentum);
+                self.running_variance = &self.running_variance * momentum + &variance * (1.0 - momentum);
+                (mean, variance)
+            }
+            false => (self.running_mean.clone(), self.running_variance.clone()),
+        };
+        let epsilon = self.epsilon;
+        self.inverse_std = variance.mapv(|v| 1.0 / (v + epsilon).sqrt());
+        self.normalized = (inputs - &mean) * &self.inverse_std;
+        self.output_values = &self.normalized * &self.scale + &self.shift;
+        self.input_values = inputs.to_owned();
+        self.batch_statistics = training;
+    }
+
+    // gradients for the given (batch_size, layer_size) deltas of the outputs
+    pub fn gradients(&self, deltas: &Array2<f32>) -> NormGradients {
+        let batch_size = deltas.nrows() as f32;
+        NormGradients {
+            scale: (deltas * &self.normalized).sum_axis(Axis(0)) / batch_size,
+            shift: deltas.sum_axis(Axis(0)) / batch_size,
+        }
+    }
+
+    // deltas propagated to the inputs, with batch statistics every input also moves the mean and
+    // variance and so the normalised values of the other samples
+    pub fn propagate(&self, deltas: &Array2<f32>) -> Array2<f32> {
+        let normalized_deltas = deltas * &self.scale;
+        if !self.batch_statistics {
+            return normalized_deltas * &self.inverse_std;
+        }
+        let mean = normalized_deltas.mean_axis(Axis(0)).unwrap();
+        let projection = (&normalized_deltas * &self.normalized).mean_axis(Axis(0)).unwrap();
+        (normalized_deltas - &mean - &self.normalized * &projection) * &self.inverse_std
+    }
+
+    pub fn apply(&mut self, gradients: &NormGradients, optimizer: &mut dyn Optimizer, layer: usize) {
+        optimizer.update((layer, 0), self.scale.view_mut().into_dyn(), gradients.scale.view().into_dyn());
+        optimizer.update((layer, 1), self.shift.view_mut().into_dyn(), gradients.shift.view().into_dyn());
+    }
+
+    // the trainable parameters in the order of NormGradients::arrays
+    pub(crate) fn params_mut(&mut self) -> Vec<ArrayViewMutD<'_, f32>> {
+        vec![self.scale.view_mut().into_dyn(), self.shift.view_mut().into_dyn()]
+    }
+}
+
+impl Layer for BatchNorm {
+    fn len_weights(&self) -> u32 {
+        0
+    }
+
+    fn batch_values(&self) -> ArrayView2<'_, f32> {
+        self.output_values.view()
+    }
+
+    fn len(&self) -> usize {
+        self.scale.len()
+    }
+
+    fn get(&self, index: usize) -> Option<Neuron> {
+        weightless_neuron(&self.input_values, &self.output_values, index)
+    }
+
+    fn get_activation(&self) -> Activation {
+        Activation::Identity
+    }
+}
+
+impl fmt::Display for BatchNorm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "scale: {}\nshift: {}", self.scale, self.shift)
+    }
+}
+
+// normalises every sample over its values to zero mean and unit variance and then applies the
+// learned gain (scale) and bias (shift), the same in training and inference mode and for any batch size
+#[derive(Debug, Clone)]
+pub struct LayerNorm {
+    pub scale: Array1<f32>,
+    pub shift: Array1<f32>,
+    // added to the variance before taking the square root
+    pub epsilon: f32,
+    // (batch_size, layer_size) values of the last forward pass
+    pub input_values: Array2<f32>,
+    pub output_values: Array2<f32>,
+    // normalised inputs and 1 / sqrt(variance + epsilon) per sample of the last forward pass
+    normalized: Array2<f32>,
+    inverse_std: Array2<f32>,
+}
+
+impl LayerNorm {
+    // keeps the size of prev_layer, starts as the identity with epsilon 1e-5
+    pub fn new(prev_layer: &dyn Layer) -> Self {
+        let layer_size = prev_layer.len();
+        Self {
+            scale: Array1::ones(layer_size),
+            shift: Array1::zeros(layer_size),
+            epsilon: 1e-5,
+            input_values: Array2::zeros((1, layer_size)),
+            output_values: Array2::zeros((1, layer_size)),
+            normalized: Array2::zeros((1, layer_size)),
+            inverse_std: Array2::ones((1, 1)),
+        }
+    }
+
+    pub fn forward(&mut self, inputs: &ArrayView2<f32>) {
+        let mean = inputs.mean_axis(Axis(1)).unwrap().insert_axis(Axis(1));
+        let variance = inputs.var_axis(Axis(1), 0.0).insert_axis(Axis(1));
+        let epsilon = self.epsilon;
+        self.inverse_std = variance.mapv(|v| 1.0 / (v + epsilon).sqrt());
+        self.normalized = (inputs - &mean) * &self.inverse_std;
+        self.output_values = &self.normalized * &self.scale + &self.shift;
+        self.input_values = inputs.to_owned();
+    }
+
+    // gradients for the given (batch_size, layer_size) deltas of the outputs
+    pub fn gradients(&self, deltas: &Array2<f32>) -> NormGradients {
+        let batch_size = deltas.nrows() as f32;
+        NormGradients {
+            scale: (deltas * &self.normalized).sum_axis(Axis(0)) / batch_size,
+            shift: deltas.sum_axis(Axis(0)) / batch_size,
+        }
+    }
+
+    // deltas propagated to the inputs, every input moves the mean and variance of its sample
+    pub fn propagate(&self, deltas: &Array2<f32>) -> Array2<f32> {
+        let normalized_deltas = deltas * &self.scale;
+        let mean = normalized_deltas.mean_axis(Axis(1)).unwrap().insert_axis(Axis(1));
+        let projection = (&normalized_deltas * &self.normalized)
+            .mean_axis(Axis(1))
+            .unwrap()
+            .insert_axis(Axis(1));
+        (normalized_deltas - &mean - &self.normalized * &projection) * &self.inverse_std
+    }
+
+    pub fn apply(&mut self, gradients: &NormGradients, optimizer: &mut dyn Optimizer, layer: usize) {
+        optimizer.update((layer, 0), self.scale.view_mut().into_dyn(), gradients.scale.view().into_dyn());
+        optimizer.update((layer, 1), self.shift.view_mut().into_dyn(), gradients.shift.view().into_dyn());
+    }
+
+    // the trainable parameters in the order of NormGradients::arrays
+    pub(crate) fn params_mut(&mut self) -> Vec<ArrayViewMutD<'_, f32>> {
+        vec![self.scale.view_mut().into_dyn(), self.shift.view_mut().into_dyn()]
+    }
+}
+
+impl Layer for LayerNorm {
+    fn len_weights(&self) -> u32 {
+        0
+    }
+
+    fn batch_values(&self) -> ArrayView2<'_, f32> {
+        self.output_values.view()
+    }
+
+    fn len(&self) -> usize {
+        self.scale.len()
+    }
+
+    fn get(&self, index: usize) -> Option<Neuron> {
+        weightless_neuron(&self.input_values, &self.output_values, index)
+    }
+
+    fn get_activation(&self) -> Activation {
+        Activation::Identity
+    }
+}
+
+impl fmt::Display for LayerNorm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "scale: {}\nshift: {}", self.scale, self.shift)
+    }
+}
+
+// divides every sample by the root mean square of its values and applies the learned gain (scale),
+// cheaper than LayerNorm as it neither centres nor shifts, the same in training and inference mode
+#[derive(Debug, Clone)]
+pub struct RmsNorm {
+    pub scale: Array1<f32>,
+    // added to the mean square before taking the square root
+    pub epsilon: f32,
+    // (batch_size, layer_size) values of the last forward pass
+    pub input_values: Array2<f32>,
+    pub output_values: Array2<f32>,
+    // normalised inputs and 1 / rms per sample of the last forward pass
+    normalized: Array2<f32>,
+    inverse_rms: Array2<f32>,
+}
+
+impl RmsNorm {
+    // keeps the size of prev_layer, starts with unit gain and epsilon 1e-5
+    pub fn new(prev_layer: &dyn Layer) -> Self {
+        let layer_size = prev_layer.len();
+        Self {
+            scale: Array1::ones(layer_size),
+            epsilon: 1e-5,
+            input_values: Array2::zeros((1, layer_size)),
+            output_values: Array2::zeros((1, layer_size)),
+            normalized: Array2::zeros((1, layer_size)),
+            inverse_rms: Array2::ones((1, 1)),
+        }
+    }
+
+    pub fn forward(&mut self, inputs: &ArrayView2<f32>) {
+        let mean_square = inputs.mapv(|v| v * v).mean_axis(Axis(1)).unwrap().insert_axis(Axis(1));
+        let epsilon = self.epsilon;
+        self.inverse_rms = mean_square.mapv(|m| 1.0 / (m + epsilon).sqrt());
+        self.normalized = inputs * &self.inverse_rms;
+        self.output_values = &self.normalized * &self.scale;
+        self.input_values = inputs.to_owned();
+    }
+
+    // gradients for the given (batch_size, layer_size) deltas of the outputs, without shift
+    pub fn gradients(&self, deltas: &Array2<f32>) -> NormGradients {
+        let batch_size = deltas.nrows() as f32;
+        NormGradients {
+            scale: (deltas * &self.normalized).sum_axis(Axis(0)) / batch_size,
+            shift: Array1::zeros(0),
+        }
+    }
+
+    // deltas propagated to the inputs, every input moves the rms of its sample
+    pub fn propagate(&self, deltas: &Array2<f32>) -> Array2<f32> {
+        let normalized_deltas = deltas * &self.scale;
+        let projection = (&normalized_deltas * &self.normalized)
+            .mean_axis(Axis(1))
+            .unwrap()
+            .insert_axis(Axis(1));
+        (normalized_deltas - &self.normalized * &projection) * &self.inverse_rms
+    }
+
+    pub fn apply(&mut self, gradients: &NormGradients, optimizer: &mut dyn Optimizer, layer: usize) {
+        optimizer.update((layer, 0), self.scale.view_mut().into_dyn(), gradients.scale.view().into_dyn());
+    }
+
+    // the trainable parameters in the order of NormGradients::arrays
+    pub(crate) fn params_mut(&mut self) -> Vec<ArrayViewMutD<'_, f32>> {
+        vec![self.scale.view_mut().into_dyn()]
+    }
+}
+
+impl Layer for RmsNorm {
+    fn len_weights(&self) -> u32 {
+        0
+    }
+
+    fn batch_values(&self) -> ArrayView2<'_, f32> {
+        self.output_values.view()
+    }
+
+    fn len(&self) -> usize {
+        self.scale.len()
+    }
+
+    fn get(&self, index: usize) -> Option<Neuron> {
+        weightless_neuron(&self.input_values, &self.output_values, index)
+    }
+
+    fn get_activation(&self) -> Activation {
+        Activation::Identity
+    }
+}
+
+impl fmt::Display for RmsNorm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "scale: {}", self.scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use crate::net::layer::InputLayer;
+
+    use super::*;
+
+    #[test]
+    fn batch_norm_test() {
+        let mut norm = BatchNorm::new(&InputLayer::new(2));
+        let inputs = array![[1.0, 10.0], [3.0, 10.0], [5.0, 10.0]];
+        norm.forward(&inputs.view(), true);
+
+        // zero mean and unit variance per column, a constant column becomes zero
+        let outputs = &norm.output_values;
+        assert!(outputs.column(0).mean().unwrap().abs() < 1e-6);
+        assert!((outputs.column(0).var(0.0) - 1.0).abs() < 1e-4);
+        assert!(outputs.column(1).iter().all(|v| v.abs() < 1e-6));
+        assert!((&norm.running_mean - &array![0.3, 1.0]).iter().all(|d| d.abs() < 1e-6));
+
+        // the deltas of a batch normalised layer sum to zero over the batch
+        let deltas = norm.propagate(&array![[1.0, 2.0], [0.0, -1.0], [4.0, 0.5]]);
+        assert!(deltas.sum_axis(Axis(0)).iter().all(|d| d.abs() < 1e-5));
+
+        norm.scale = array![2.0, 1.0];
+        norm.shift = array![0.5, -1.0];
+        norm.running_mean = array![3.0, 0.0];
+        norm.running_variance = array![4.0, 1.0];
+        norm.forward(&array![[5.0, 2.0]].view(), false);
+        assert!((&norm.output_values - &array![[2.5, 1.0]]).iter().all(|d| d.abs() < 1e-4));
+        let deltas = norm.propagate(&array![[1.0, 1.0]]);
+        assert!((&deltas - &array![[1.0, 1.0]]).iter().all(|d| d.abs() < 1e-4));
+    }
+
+    #[test]
+    fn layer_norm_test() {
+        let input = InputLayer::new(4);
+        let mut norm = LayerNorm::new(&input);
+        // a single sample is normalised over its own values
+        norm.forward(&array![[1.0, 2.0, 3.0, 6.0]].view());
+        let outputs = norm.output_values.clone();
+        assert!(outputs.mean().unwrap().abs() < 1e-6);
+        assert!((outputs.var(0.0) - 1.0).abs() < 1e-4);
+
+        // every sample of a batch independent of the others
+        norm.forward(&array![[1.0, 2.0, 3.0, 6.0], [-5.0, 0.0, 5.0, 100.0]].view());
+        assert_eq!(outputs.row(0), norm.output_values.row(0));
+        let deltas = norm.propagate(&array![[1.0, 0.0, -2.0, 0.5], [0.0, 0.0, 0.0, 0.0]]);
+        assert!(deltas.row(0).sum().abs() < 1e-5);
+        assert_eq!(array![0.0, 0.0, 0.0, 0.0], deltas.row(1));
+
+        let mut rms = RmsNorm::new(&input);
+        rms.scale = array![1.0, 1.0, 2.0, 1.0];
+        rms.forward(&array![[3.0, -3.0, 3.0, 3.0]].view());
+        assert!((&rms.output_values - &array![[1.0, -1.0, 2.0, 1.0]]).iter().all(|d| d.abs() < 1e-5));
+        // scaling a sample does not change its normalised values, so its own direction gets no deltas
+        let deltas = rms.propagate(&array![[3.0, -3.0, 1.5, 3.0]]);
+        assert!(deltas.iter().all(|d| d.abs() < 1e-5), "{}", deltas);
+        let gradients = rms.gradients(&array![[1.0, 0.0, 1.0, 0.0]]);
+        assert!((&gradients.scale - &array![1.0, 0.0, 1.0, 0.0]).iter().all(|d| d.abs() < 1e-5));
+        assert_eq!(1, gradients.arrays().len());
+    }
+}
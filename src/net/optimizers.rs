@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use ndarray::{ArrayD, ArrayViewD, ArrayViewMutD, Zip};
+
+// identifies a parameter across steps: (layer index, parameter index inside the layer)
+pub type ParamKey = (usize, usize);
+
+pub trait Optimizer: Debug {
+    fn learning_rate(&self) -> f32;
+
+    fn set_learning_rate(&mut self, learning_rate: f32);
+
+    // called once per update, before the parameters of the network are passed to update
+    fn begin_step(&mut self) {}
+
+    // apply the gradient to the parameter in place, state is kept per key
+    fn update(&mut self, key: ParamKey, param: ArrayViewMutD<f32>, gradient: ArrayViewD<f32>);
+}
+
+// returns the state stored for key, initialised with zeros shaped like the gradient
+fn slot<'a>(state: &'a mut HashMap<ParamKey, ArrayD<f32>>, key: ParamKey, gradient: &ArrayViewD<f32>) -> &'a mut ArrayD<f32> {
+    let slot = state
+        .entry(key)
+        .or_insert_with(|| ArrayD::zeros(gradient.raw_dim()));
+    if slot.shape() != gradient.shape() {
+        *slot = ArrayD::zeros(gradient.raw_dim());
+    }
+    slot
+}
+
+// stochastic gradient descent with optional (nesterov) momentum
+#[derive(Debug, Clone)]
+pub struct Sgd {
+    pub learning_rate: f32,
+    pub momentum: f32,
+    pub nesterov: bool,
+    velocity: HashMap<ParamKey, ArrayD<f32>>,
+}
+
+impl Sgd {
+    pub fn new(learning_rate: f32) -> Self {
+        Self::with_momentum(learning_rate, 0.0)
+    }
+
+    pub fn with_momentum(learning_rate: f32, momentum: f32) -> Self {
+        Self {
+            learning_rate,
+            momentum,
+            nesterov: false,
+            velocity: HashMap::new(),
+        }
+    }
+
+    // evaluates the gradient at the position the momentum is about to move to
+    pub fn nesterov(learning_rate: f32, momentum: f32) -> Self {
+        Self {
+            nesterov: true,
+            ..Self::with_momentum(learning_rate, momentum)
+        }
+    }
+}
+
+impl Optimizer for Sgd {
+    fn learning_rate(&self) -> f32 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.learning_rate = learning_rate;
+    }
+
+    fn update(&mut self, key: ParamKey, mut param: ArrayViewMutD<f32>, gradient: ArrayViewD<f32>) {
+        let (lr, momentum) = (self.learning_rate, self.momentum);
+        if momentum == 0.0 {
+            param.scaled_add(-lr, &gradient);
+            return;
+        }
+
+        // v = momentum * v - lr * g
+        let velocity = slot(&mut self.velocity, key, &gradient);
+        Zip::from(&mut *velocity)
+            .and(&gradient)
+            .for_each(|v, g| *v = momentum * *v - lr * g);
+        if self.nesterov {
+            // w += momentum * v - lr * g
+            Zip::from(&mut param)
+                .and(&*velocity)
+                .and(&gradient)
+                .for_each(|w, v, g| *w += momentum * v - lr * g);
+        } else {
+            param += &*velocity;
+        }
+    }
+}
+
+// scales the learning rate by a moving average of the squared gradients
+#[derive(Debug, Clone)]
+pub struct RmsProp {
+    pub learning_rate: f32,
+    pub rho: f32,
+    pub epsilon: f32,
+    mean_square: HashMap<ParamKey, ArrayD<f32>>,
+}
+
+impl RmsProp {
+    pub fn new(learning_rate: f32) -> Self {
+        Self {
+            learning_rate,
+            rho: 0.9,
+            epsilon: 1e-7,
+            mean_square: HashMap::new(),
+        }
+    }
+}
+
+impl Optimizer for RmsProp {
+    fn learning_rate(&self) -> f32 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.learning_rate = learning_rate;
+    }
+
+    fn update(&mut self, key: ParamKey, mut param: ArrayViewMutD<f32>, gradient: ArrayViewD<f32>) {
+        let (lr, rho, eps) = (self.learning_rate, self.rho, self.epsilon);
+        let mean_square = slot(&mut self.mean_square, key, &gradient);
+        Zip::from(&mut param)
+            .and(mean_square)
+            .and(&gradient)
+            .for_each(|w, s, g| {
+                *s = rho * *s + (1.0 - rho) * g * g;
+                *w -= lr * g / (s.sqrt() + eps);
+            });
+    }
+}
+
+// scales the learning rate by the sum of all squared gradients seen so far
+#[derive(Debug, Clone)]
+pub struct Adagrad {
+    pub learning_rate: f32,
+    pub epsilon: f32,
+    sum_square: HashMap<ParamKey, ArrayD<f32>>,
+}
+
+impl Adagrad {
+    pub fn new(learning_rate: f32) -> Self {
+        Self {
+            learning_rate,
+            epsilon: 1e-7,
+            sum_square: HashMap::new(),
+        }
+    }
+}
+
+impl Optimizer for Adagrad {
+    fn learning_rate(&self) -> f32 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.learning_rate = learning_rate;
+    }
+
+    fn update(&mut self, key: ParamKey, mut param: ArrayViewMutD<f32>, gradient: ArrayViewD<f32>) {
+        let (lr, eps) = (self.learning_rate, self.epsilon);
+        let sum_square = slot(&mut self.sum_square, key, &gradient);
+        Zip::from(&mut param)
+            .and(sum_square)
+            .and(&gradient)
+            .for_each(|w, s, g| {
+                *s += g * g;
+                *w -= lr * g / (s.sqrt() + eps);
+            });
+    }
+}
+
+// bias corrected moving averages of the gradient and the squared gradient
+#[derive(Debug, Clone)]
+pub struct Adam {
+    pub learning_rate: f32,
+    pub beta1: f32,
+    pub beta2: f32,
+    pub epsilon: f32,
+    step: i32,
+    first_moment: HashMap<ParamKey, ArrayD<f32>>,
+    second_moment: HashMap<ParamKey, ArrayD<f32>>,
+}
+
+impl Adam {
+    pub fn new(learning_rate: f32) -> Self {
+        Self {
+            learning_rate,
+            beta1: 0.9,
+            beta2: 0.999,
+            epsilon: 1e-8,
+            step: 0,
+            first_moment: HashMap::new(),
+            second_moment: HashMap::new(),
+        }
+    }
+}
+
+impl Optimizer for Adam {
+    fn learning_rate(&self) -> f32 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.learning_rate = learning_rate;
+    }
+
+    fn begin_step(&mut self) {
+        self.step += 1;
+    }
+
+    fn update(&mut self, key: ParamKey, mut param: ArrayViewMutD<f32>, gradient: ArrayViewD<f32>) {
+        let (lr, beta1, beta2, eps) = (self.learning_rate, self.beta1, self.beta2, self.epsilon);
+        let step = self.step.max(1);
+        let correction1 = 1.0 - beta1.powi(step);
+        let correction2 = 1.0 - beta2.powi(step);
+
+        let m = slot(&mut self.first_moment, key, &gradient);
+        let v = slot(&mut self.second_moment, key, &gradient);
+        Zip::from(&mut param)
+            .and(m)
+            .and(v)
+            .and(&gradient)
+            .for_each(|w, m, v, g| {
+                *m = beta1 * *m + (1.0 - beta1) * g;
+                *v = beta2 * *v + (1.0 - beta2) * g * g;
+                *w -= lr * (*m / correction1) / ((*v / correction2).sqrt() + eps);
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{arr1, Array1};
+
+    use super::*;
+
+    // minimise f(w) = sum(w^2) from a fixed start and return the final parameters
+    fn minimise(optimizer: &mut dyn Optimizer, steps: usize) -> Array1<f32> {
+        let mut w = arr1(&[1.0, -2.0]);
+        for _ in 0..steps {
+            let gradient = w.mapv(|w| 2.0 * w);
+            optimizer.begin_step();
+            optimizer.update((0, 0), w.view_mut().into_dyn(), gradient.view().into_dyn());
+        }
+        w
+    }
+
+    #[test]
+    fn sgd_test() {
+        let mut sgd = Sgd::new(0.25);
+        let w = minimise(&mut sgd, 1);
+        assert_eq!(arr1(&[0.5, -1.0]), w);
+    }
+
+    #[test]
+    fn momentum_test() {
+        // the second step moves further than plain sgd because of the velocity
+        let mut momentum = Sgd::with_momentum(0.1, 0.9);
+        let mut sgd = Sgd::new(0.1);
+        assert!(minimise(&mut momentum, 2)[0] < minimise(&mut sgd, 2)[0]);
+    }
+
+    #[test]
+    fn convergence_test() {
+        let optimizers: Vec<Box<dyn Optimizer>> = vec![
+            Box::new(Sgd::new(0.1)),
+            Box::new(Sgd::with_momentum(0.05, 0.9)),
+            Box::new(Sgd::nesterov(0.05, 0.9)),
+            Box::new(RmsProp::new(0.01)),
+            Box::new(Adagrad::new(0.5)),
+            Box::new(Adam::new(0.05)),
+        ];
+        for mut optimizer in optimizers {
+            let w = minimise(optimizer.as_mut(), 500);
+            assert!(w.iter().all(|w| w.abs() < 0.05), "{:?}: {}", optimizer, w);
+        }
+    }
+
+    #[test]
+    fn adam_first_step_test() {
+        // bias correction makes the first step exactly lr * sign(g)
+        let mut adam = Adam::new(0.1);
+        let w = minimise(&mut adam, 1);
+        assert!((w[0] - 0.9).abs() < 1e-6);
+        assert!((w[1] + 1.9).abs() < 1e-6);
+    }
+}
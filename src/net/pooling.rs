@@ -0,0 +1,414 @@
+use std::fmt;
+
+use ndarray::prelude::*;
+
+use super::activation_functions::Activation;
+use super::conv::{output_len, Shape2, Shape3};
+use super::layer::{weightless_neuron, Layer};
+use super::neuron::Neuron;
+
+// the (height, width) of a 2d pooling window and of the steps between windows, without padding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pool2D {
+    pub pool: (usize, usize),
+    pub stride: (usize, usize),
+    pub input_shape: Shape3,
+}
+
+impl Pool2D {
+    pub fn output_shape(&self) -> Shape3 {
+        let (channels, height, width) = self.input_shape;
+        (
+            channels,
+            output_len(height, self.pool.0, self.stride.0, (0, 0)),
+            output_len(width, self.pool.1, self.stride.1, (0, 0)),
+        )
+    }
+
+    pub fn output_len(&self) -> usize {
+        let (channels, height, width) = self.output_shape();
+        channels * height * width
+    }
+
+    // calls f(output index, input index) for every value of every window of one flattened sample
+    fn for_each(&self, mut f: impl FnMut(usize, usize)) {
+        let (channels, height, width) = self.input_shape;
+        let (_, out_h, out_w) = self.output_shape();
+        let ((ph, pw), (sh, sw)) = (self.pool, self.stride);
+        for channel in 0..channels {
+            for oy in 0..out_h {
+                for ox in 0..out_w {
+                    let output = (channel * out_h + oy) * out_w + ox;
+                    for y in oy * sh..oy * sh + ph {
+                        for x in ox * sw..ox * sw + pw {
+                            f(output, (channel * height + y) * width + x);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Pool2D {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?} -> {:?}, pool {:?}, stride {:?}",
+            self.input_shape,
+            self.output_shape(),
+            self.pool,
+            self.stride
+        )
+    }
+}
+
+// the largest value of every window of every channel
+#[derive(Debug, Clone)]
+pub struct MaxPool2D {
+    pub window: Pool2D,
+    // (batch_size, channels * height * width) values of the last forward pass
+    pub output_values: Array2<f32>,
+    // (batch_size, layer_size) index of the input each output of the last forward pass was taken from
+    pub switches: Array2<usize>,
+}
+
+impl MaxPool2D {
+    pub fn new(input_shape: Shape3, pool: (usize, usize), stride: (usize, usize)) -> Self {
+        let window = Pool2D {
+            pool,
+            stride,
+            input_shape,
+        };
+        let layer_size = window.output_len();
+        Self {
+            window,
+            output_values: Array2::zeros((1, layer_size)),
+            switches: Array2::zeros((1, layer_size)),
+        }
+    }
+
+    pub fn forward(&mut self, inputs: &ArrayView2<f32>) {
+        let shape = (inputs.nrows(), self.window.output_len());
+        self.output_values = Array2::from_elem(shape, f32::NEG_INFINITY);
+        self.switches = Array2::zeros(shape);
+        for (n, inputs) in inputs.rows().into_iter().enumerate() {
+            let (mut outputs, mut switches) = (self.output_values.row_mut(n), self.switches.row_mut(n));
+            self.window.for_each(|output, input| {
+                // NaN wins so that it reaches the non-finite check of the network
+                if inputs[input] > outputs[output] || inputs[input].is_nan() {
+                    outputs[output] = inputs[input];
+                    switches[output] = input;
+                }
+            });
+        }
+    }
+
+    // the deltas flow back only to the maxima
+    pub fn propagate(&self, deltas: &Array2<f32>) -> Array2<f32> {
+        let (channels, height, width) = self.window.input_shape;
+        let mut propagated = Array2::zeros((deltas.nrows(), channels * height * width));
+        for ((n, output), input) in self.switches.indexed_iter() {
+            propagated[[n, *input]] += deltas[[n, output]];
+        }
+        propagated
+    }
+}
+
+impl Layer for MaxPool2D {
+    fn len_weights(&self) -> u32 {
+        0
+    }
+
+    fn batch_values(&self) -> ArrayView2<'_, f32> {
+        self.output_values.view()
+    }
+
+    fn len(&self) -> usize {
+        self.window.output_len()
+    }
+
+    fn get(&self, index: usize) -> Option<Neuron> {
+        weightless_neuron(&self.output_values, &self.output_values, index)
+    }
+
+    fn get_activation(&self) -> Activation {
+        Activation::Identity
+    }
+}
+
+impl fmt::Display for MaxPool2D {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "max_pool2d: {}", self.window)
+    }
+}
+
+// the mean of every window of every channel
+#[derive(Debug, Clone)]
+pub struct AvgPool2D {
+    pub window: Pool2D,
+    // (batch_size, channels * height * width) values of the last forward pass
+    pub output_values: Array2<f32>,
+}
+
+impl AvgPool2D {
+    pub fn new(input_shape: Shape3, pool: (usize, usize), stride: (usize, usize)) -> Self {
+        let window = Pool2D {
+            pool,
+            stride,
+            input_shape,
+        };
+        Self {
+            window,
+            output_values: Array2::zeros((1, window.output_len())),
+        }
+    }
+
+    fn area(&self) -> f32 {
+        (self.window.pool.0 * self.window.pool.1) as f32
+    }
+
+    pub fn forward(&mut self, inputs: &ArrayView2<f32>) {
+        let scale = 1.0 / self.area();
+        self.output_values = Array2::zeros((inputs.nrows(), self.window.output_len()));
+        for (inputs, mut outputs) in inputs.rows().into_iter().zip(self.output_values.rows_mut()) {
+            self.window
+                .for_each(|output, input| outputs[output] += inputs[input] * scale);
+        }
+    }
+
+    // every value of a window gets an equal share of its delta
+    pub fn propagate(&self, deltas: &Array2<f32>) -> Array2<f32> {
+        let scale = 1.0 / self.area();
+        let (channels, height, width) = self.window.input_shape;
+        let mut propagated = Array2::zeros((deltas.nrows(), channels * height * width));
+        for (deltas, mut propagated) in deltas.rows().into_iter().zip(propagated.rows_mut()) {
+            self.window
+                .for_each(|output, input| propagated[input] += deltas[output] * scale);
+        }
+        propagated
+    }
+}
+
+impl Layer for AvgPool2D {
+    fn len_weights(&self) -> u32 {
+        0
+    }
+
+    fn batch_values(&self) -> ArrayView2<'_, f32> {
+        self.output_values.view()
+    }
+
+    fn len(&self) -> usize {
+        self.window.output_len()
+    }
+
+    fn get(&self, index: usize) -> Option<Neuron> {
+        weightless_neuron(&self.output_values, &self.output_values, index)
+    }
+
+    fn get_activation(&self) -> Activation {
+        Activation::Identity
+    }
+}
+
+impl fmt::Display for AvgPool2D {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "avg_pool2d: {}", self.window)
+    }
+}
+
+// the mean of every channel of a (channels, length) sequence over all its positions
+#[derive(Debug, Clone)]
+pub struct GlobalAveragePool1D {
+    pub input_shape: Shape2,
+    // (batch_size, channels) values of the last forward pass
+    pub output_values: Array2<f32>,
+}
+
+impl GlobalAveragePool1D {
+    pub fn new(input_shape: Shape2) -> Self {
+        Self {
+            input_shape,
+            output_values: Array2::zeros((1, input_shape.0)),
+        }
+    }
+
+    pub fn forward(&mut self, inputs: &ArrayView2<f32>) {
+        let (channels, length) = self.input_shape;
+        self.output_values = inputs
+            .as_standard_layout()
+            .into_owned()
+            .into_shape((inputs.nrows(), channels, length))
+            .expect("one sequence per row")
+            .mean_axis(Axis(2))
+            .expect("sequences are not empty");
+    }
+
+    // every position gets an equal share of the delta of its channel
+    pub fn propagate(&self, deltas: &Array2<f32>) -> Array2<f32> {
+        let (channels, length) = self.input_shape;
+        let shares = deltas / length as f32;
+        shares
+            .insert_axis(Axis(2))
+            .broadcast((deltas.nrows(), channels, length))
+            .expect("one delta per channel")
+            .as_standard_layout()
+            .into_owned()
+            .into_shape((deltas.nrows(), channels * length))
+            .expect("standard layout")
+    }
+}
+
+impl Layer for GlobalAveragePool1D {
+    fn len_weights(&self) -> u32 {
+        0
+    }
+
+    fn batch_values(&self) -> ArrayView2<'_, f32> {
+        self.output_values.view()
+    }
+
+    fn len(&self) -> usize {
+        self.input_shape.0
+    }
+
+    fn get(&self, index: usize) -> Option<Neuron> {
+        weightless_neuron(&self.output_values, &self.output_values, index)
+    }
+
+    fn get_activation(&self) -> Activation {
+        Activation::Identity
+    }
+}
+
+impl fmt::Display for GlobalAveragePool1D {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "global_average_pool1d: {:?}", self.input_shape)
+    }
+}
+
+// the largest value of every channel of a (channels, length) sequence
+#[derive(Debug, Clone)]
+pub struct GlobalMaxPool1D {
+    pub input_shape: Shape2,
+    // (batch_size, channels) values of the last forward pass
+    pub output_values: Array2<f32>,
+    // (batch_size, channels) position each output of the last forward pass was taken from
+    pub switches: Array2<usize>,
+}
+
+impl GlobalMaxPool1D {
+    pub fn new(input_shape: Shape2) -> Self {
+        Self {
+            input_shape,
+            output_values: Array2::zeros((1, input_shape.0)),
+            switches: Array2::zeros((1, input_shape.0)),
+        }
+    }
+
+    pub fn forward(&mut self, inputs: &ArrayView2<f32>) {
+        let (channels, length) = self.input_shape;
+        let shape = (inputs.nrows(), channels);
+        self.output_values = Array2::from_elem(shape, f32::NEG_INFINITY);
+        self.switches = Array2::zeros(shape);
+        for ((n, channel), output) in self.output_values.indexed_iter_mut() {
+            for position in 0..length {
+                let value = inputs[[n, channel * length + position]];
+                // NaN wins so that it reaches the non-finite check of the network
+                if value > *output || value.is_nan() {
+                    *output = value;
+                    self.switches[[n, channel]] = position;
+                }
+            }
+        }
+    }
+
+    // the deltas flow back only to the maxima
+    pub fn propagate(&self, deltas: &Array2<f32>) -> Array2<f32> {
+        let (channels, length) = self.input_shape;
+        let mut propagated = Array2::zeros((deltas.nrows(), channels * length));
+        for ((n, channel), position) in self.switches.indexed_iter() {
+            propagated[[n, channel * length + position]] = deltas[[n, channel]];
+        }
+        propagated
+    }
+}
+
+impl Layer for GlobalMaxPool1D {
+    fn len_weights(&self) -> u32 {
+        0
+    }
+
+    fn batch_values(&self) -> ArrayView2<'_, f32> {
+        self.output_values.view()
+    }
+
+    fn len(&self) -> usize {
+        self.input_shape.0
+    }
+
+    fn get(&self, index: usize) -> Option<Neuron> {
+        weightless_neuron(&self.output_values, &self.output_values, index)
+    }
+
+    fn get_activation(&self) -> Activation {
+        Activation::Identity
+    }
+}
+
+impl fmt::Display for GlobalMaxPool1D {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "global_max_pool1d: {:?}", self.input_shape)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pool_test() {
+        // the last row does not fill a window and is dropped
+        let mut max = MaxPool2D::new((1, 3, 4), (2, 2), (2, 2));
+        assert_eq!((1, 1, 2), max.window.output_shape());
+        let inputs = array![[1.0, 2.0, 5.0, 0.0, 4.0, -1.0, 6.0, 7.0, 9.0, 9.0, 9.0, 9.0]];
+
+        max.forward(&inputs.view());
+        assert_eq!(array![[4.0, 7.0]], max.output_values);
+        assert_eq!(
+            array![[0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.0]],
+            max.propagate(&array![[1.0, 2.0]])
+        );
+
+        let mut avg = AvgPool2D::new((1, 3, 4), (2, 2), (2, 2));
+        avg.forward(&inputs.view());
+        assert_eq!(array![[1.5, 4.5]], avg.output_values);
+        assert_eq!(
+            array![[0.25, 0.25, 0.5, 0.5, 0.25, 0.25, 0.5, 0.5, 0.0, 0.0, 0.0, 0.0]],
+            avg.propagate(&array![[1.0, 2.0]])
+        );
+
+        // overlapping windows add up their deltas
+        let mut avg = AvgPool2D::new((1, 1, 3), (1, 2), (1, 1));
+        avg.forward(&array![[1.0, 3.0, 5.0]].view());
+        assert_eq!(array![[2.0, 4.0]], avg.output_values);
+        assert_eq!(array![[0.5, 1.0, 0.5]], avg.propagate(&array![[1.0, 1.0]]));
+    }
+
+    #[test]
+    fn global_pool_test() {
+        // two channels of three positions
+        let inputs = array![[1.0, 5.0, 3.0, -2.0, -1.0, -4.0]];
+
+        let mut max = GlobalMaxPool1D::new((2, 3));
+        max.forward(&inputs.view());
+        assert_eq!(array![[5.0, -1.0]], max.output_values);
+        assert_eq!(array![[0.0, 2.0, 0.0, 0.0, 3.0, 0.0]], max.propagate(&array![[2.0, 3.0]]));
+
+        let mut avg = GlobalAveragePool1D::new((2, 3));
+        avg.forward(&inputs.view());
+        assert_eq!(array![[3.0, -7.0 / 3.0]], avg.output_values);
+        assert_eq!(array![[1.0, 1.0, 1.0, 2.0, 2.0, 2.0]], avg.propagate(&array![[3.0, 6.0]]));
+    }
+}
@@ -0,0 +1,168 @@
+use ndarray::{Array1, Array2, Axis, Zip};
+use serde::{Deserialize, Serialize};
+
+use super::layer::DenseGradients;
+
+// regularisation of the weights of one dense layer, everything is off by default
+//
+//  Regularizer::default().l2(1e-4).max_norm(3.0)
+//
+// l1 and l2 add l1 * sum(|w|) + l2 * sum(w^2) to the loss and their gradients to the backward pass,
+// weight_decay shrinks the weights by learning_rate * weight_decay before every optimizer step
+// independent of the gradients (decoupled as in AdamW), max_norm and non_negative constrain the
+// weights after every step. biases are left alone unless include_bias is set
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Regularizer {
+    pub l1: f32,
+    pub l2: f32,
+    pub weight_decay: f32,
+    // upper bound for the norm of the incoming weights of every neuron
+    pub max_norm: Option<f32>,
+    pub non_negative: bool,
+    pub include_bias: bool,
+}
+
+impl Regularizer {
+    pub fn l1(mut self, l1: f32) -> Self {
+        self.l1 = l1;
+        self
+    }
+
+    pub fn l2(mut self, l2: f32) -> Self {
+        self.l2 = l2;
+        self
+    }
+
+    pub fn weight_decay(mut self, weight_decay: f32) -> Self {
+        self.weight_decay = weight_decay;
+        self
+    }
+
+    pub fn max_norm(mut self, max_norm: f32) -> Self {
+        self.max_norm = Some(max_norm);
+        self
+    }
+
+    pub fn non_negative(mut self, non_negative: bool) -> Self {
+        self.non_negative = non_negative;
+        self
+    }
+
+    // also apply the penalties, the decay and the non-negativity to the biases
+    pub fn include_bias(mut self, include_bias: bool) -> Self {
+        self.include_bias = include_bias;
+        self
+    }
+
+    fn penalty_of<'a>(&self, values: impl Iterator<Item = &'a f32>) -> f32 {
+        values.map(|w| self.l1 * w.abs() + self.l2 * w * w).sum()
+    }
+
+    // the l1 and l2 terms added to the loss
+    pub fn penalty(&self, weights: &Array2<f32>, biases: &Array1<f32>) -> f32 {
+        if self.l1 == 0.0 && self.l2 == 0.0 {
+            return 0.0;
+        }
+        let mut penalty = self.penalty_of(weights.iter());
+        if self.include_bias {
+            penalty += self.penalty_of(biases.iter());
+        }
+        penalty
+    }
+
+    // add the gradients of the penalty, bias tells whether the layer trains its biases at all
+    pub fn add_gradients(&self, weights: &Array2<f32>, biases: &Array1<f32>, bias: bool, gradients: &mut DenseGradients) {
+        if self.l1 == 0.0 && self.l2 == 0.0 {
+            return;
+        }
+        let (l1, l2) = (self.l1, self.l2);
+        let gradient = |g: &mut f32, w: &f32| *g += l1 * sign(*w) + 2.0 * l2 * w;
+        Zip::from(&mut gradients.weights).and(weights).for_each(gradient);
+        if bias && self.include_bias {
+            Zip::from(&mut gradients.biases).and(biases).for_each(gradient);
+        }
+    }
+
+    // the decoupled weight decay, applied before the optimizer step
+    pub fn decay(&self, learning_rate: f32, weights: &mut Array2<f32>, biases: &mut Array1<f32>) {
+        if self.weight_decay == 0.0 {
+            return;
+        }
+        let factor = 1.0 - learning_rate * self.weight_decay;
+        *weights *= factor;
+        if self.include_bias {
+            *biases *= factor;
+        }
+    }
+
+    // the constraints, applied after the optimizer step
+    pub fn constrain(&self, weights: &mut Array2<f32>, biases: &mut Array1<f32>) {
+        if let Some(max_norm) = self.max_norm {
+            for mut column in weights.axis_iter_mut(Axis(1)) {
+                let norm = column.dot(&column).sqrt();
+                if norm > max_norm {
+                    column *= max_norm / norm;
+                }
+            }
+        }
+        if self.non_negative {
+            weights.mapv_inplace(|w| w.max(0.0));
+            if self.include_bias {
+                biases.mapv_inplace(|b| b.max(0.0));
+            }
+        }
+    }
+}
+
+// sign without the jump of f32::signum at 0
+fn sign(w: f32) -> f32 {
+    if w == 0.0 {
+        0.0
+    } else {
+        w.signum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+
+    #[test]
+    fn penalty_test() {
+        let weights = array![[1.0, -2.0], [0.0, 0.5]];
+        let biases = array![3.0, -1.0];
+        let regularizer = Regularizer::default().l1(0.1).l2(0.01);
+        // 0.1 * 3.5 + 0.01 * 5.25
+        assert!((regularizer.penalty(&weights, &biases) - 0.4025).abs() < 1e-6);
+        let with_bias = regularizer.include_bias(true).penalty(&weights, &biases);
+        assert!((with_bias - 0.4025 - 0.4 - 0.1).abs() < 1e-6);
+
+        let mut gradients = DenseGradients {
+            weights: Array2::zeros((2, 2)),
+            biases: Array1::zeros(2),
+        };
+        regularizer.add_gradients(&weights, &biases, true, &mut gradients);
+        let expected = array![[0.12, -0.14], [0.0, 0.11]];
+        assert!((&gradients.weights - &expected).iter().all(|d| d.abs() < 1e-6));
+        assert_eq!(array![0.0, 0.0], gradients.biases);
+    }
+
+    #[test]
+    fn constraint_test() {
+        let mut weights = array![[3.0, -0.6], [4.0, 0.5]];
+        let mut biases = array![-1.0, 2.0];
+        Regularizer::default()
+            .max_norm(1.0)
+            .non_negative(true)
+            .constrain(&mut weights, &mut biases);
+        let expected = array![[0.6, 0.0], [0.8, 0.5]];
+        assert!((&weights - &expected).iter().all(|d| d.abs() < 1e-6));
+        assert_eq!(array![-1.0, 2.0], biases);
+
+        Regularizer::default().weight_decay(0.5).decay(0.1, &mut weights, &mut biases);
+        assert!((weights[[0, 0]] - 0.57).abs() < 1e-6);
+        assert_eq!(array![-1.0, 2.0], biases);
+    }
+}
@@ -0,0 +1,253 @@
+use std::f32::consts::PI;
+use std::fmt::Debug;
+
+use super::optimizers::Optimizer;
+
+// a learning rate as a function of the step, counted from 0
+// whether a step is a batch or an epoch is up to the caller
+pub trait LrSchedule: Debug + Send + Sync {
+    fn learning_rate(&self, step: usize) -> f32;
+
+    // set the learning rate of the optimizer for the given step
+    fn apply(&self, optimizer: &mut dyn Optimizer, step: usize) {
+        optimizer.set_learning_rate(self.learning_rate(step));
+    }
+}
+
+// cosine interpolation from start to end, progress runs from 0 to 1
+fn cosine(start: f32, end: f32, progress: f32) -> f32 {
+    end + (start - end) * 0.5 * (1.0 + (PI * progress.clamp(0.0, 1.0)).cos())
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Constant {
+    pub learning_rate: f32,
+}
+
+impl Constant {
+    pub fn new(learning_rate: f32) -> Self {
+        Self { learning_rate }
+    }
+}
+
+impl LrSchedule for Constant {
+    fn learning_rate(&self, _step: usize) -> f32 {
+        self.learning_rate
+    }
+}
+
+// multiplies the learning rate by factor every step_size steps
+#[derive(Debug, Clone, Copy)]
+pub struct StepDecay {
+    pub initial: f32,
+    pub factor: f32,
+    pub step_size: usize,
+}
+
+impl StepDecay {
+    pub fn new(initial: f32, factor: f32, step_size: usize) -> Self {
+        Self {
+            initial,
+            factor,
+            step_size: step_size.max(1),
+        }
+    }
+}
+
+impl LrSchedule for StepDecay {
+    fn learning_rate(&self, step: usize) -> f32 {
+        self.initial * self.factor.powi((step / self.step_size) as i32)
+    }
+}
+
+// initial * rate ^ (step / decay_steps), decaying smoothly on every step
+#[derive(Debug, Clone, Copy)]
+pub struct ExponentialDecay {
+    pub initial: f32,
+    pub rate: f32,
+    pub decay_steps: usize,
+}
+
+impl ExponentialDecay {
+    pub fn new(initial: f32, rate: f32, decay_steps: usize) -> Self {
+        Self {
+            initial,
+            rate,
+            decay_steps: decay_steps.max(1),
+        }
+    }
+}
+
+impl LrSchedule for ExponentialDecay {
+    fn learning_rate(&self, step: usize) -> f32 {
+        self.initial * self.rate.powf(step as f32 / self.decay_steps as f32)
+    }
+}
+
+// anneals from max_lr to min_lr along a half cosine over period steps and then restarts at max_lr,
+// every period is period_mult times longer than the previous one
+#[derive(Debug, Clone, Copy)]
+pub struct CosineAnnealing {
+    pub max_lr: f32,
+    pub min_lr: f32,
+    pub period: usize,
+    pub period_mult: usize,
+}
+
+impl CosineAnnealing {
+    pub fn new(max_lr: f32, min_lr: f32, period: usize) -> Self {
+        Self {
+            max_lr,
+            min_lr,
+            period: period.max(1),
+            period_mult: 1,
+        }
+    }
+
+    pub fn with_period_mult(mut self, period_mult: usize) -> Self {
+        self.period_mult = period_mult.max(1);
+        self
+    }
+}
+
+impl LrSchedule for CosineAnnealing {
+    fn learning_rate(&self, step: usize) -> f32 {
+        // find the restart the step falls into
+        let (mut start, mut period) = (0, self.period);
+        while step >= start + period {
+            start += period;
+            period *= self.period_mult;
+        }
+        cosine(self.max_lr, self.min_lr, (step - start) as f32 / period as f32)
+    }
+}
+
+// ramps linearly from start_lr to the wrapped schedule over warmup_steps,
+// after that the wrapped schedule runs as if it started at step 0
+#[derive(Debug)]
+pub struct LinearWarmup<S: LrSchedule> {
+    pub start_lr: f32,
+    pub warmup_steps: usize,
+    pub schedule: S,
+}
+
+impl<S: LrSchedule> LinearWarmup<S> {
+    pub fn new(warmup_steps: usize, schedule: S) -> Self {
+        Self {
+            start_lr: 0.0,
+            warmup_steps,
+            schedule,
+        }
+    }
+}
+
+impl<S: LrSchedule> LrSchedule for LinearWarmup<S> {
+    fn learning_rate(&self, step: usize) -> f32 {
+        if step >= self.warmup_steps {
+            return self.schedule.learning_rate(step - self.warmup_steps);
+        }
+        let target = self.schedule.learning_rate(0);
+        self.start_lr + (target - self.start_lr) * step as f32 / self.warmup_steps as f32
+    }
+}
+
+// rises from max_lr / div_factor to max_lr over the first pct_start of total_steps,
+// then anneals to max_lr / (div_factor * final_div_factor)
+#[derive(Debug, Clone, Copy)]
+pub struct OneCycle {
+    pub max_lr: f32,
+    pub total_steps: usize,
+    pub pct_start: f32,
+    pub div_factor: f32,
+    pub final_div_factor: f32,
+}
+
+impl OneCycle {
+    pub fn new(max_lr: f32, total_steps: usize) -> Self {
+        Self {
+            max_lr,
+            total_steps: total_steps.max(2),
+            pct_start: 0.3,
+            div_factor: 25.0,
+            final_div_factor: 1e4,
+        }
+    }
+}
+
+impl LrSchedule for OneCycle {
+    fn learning_rate(&self, step: usize) -> f32 {
+        let initial = self.max_lr / self.div_factor;
+        let last = (self.total_steps - 1) as f32;
+        let peak = (self.pct_start * last).max(1.0);
+        let step = step as f32;
+        if step <= peak {
+            cosine(initial, self.max_lr, step / peak)
+        } else {
+            let end = initial / self.final_div_factor;
+            cosine(self.max_lr, end, (step - peak) / (last - peak).max(1.0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::optimizers::Sgd;
+
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn decay_test() {
+        let step = StepDecay::new(0.1, 0.5, 10);
+        assert!(close(0.1, step.learning_rate(9)));
+        assert!(close(0.05, step.learning_rate(10)));
+        assert!(close(0.025, step.learning_rate(25)));
+
+        let exponential = ExponentialDecay::new(0.1, 0.5, 10);
+        assert!(close(0.1, exponential.learning_rate(0)));
+        assert!(close(0.05, exponential.learning_rate(10)));
+        assert!(exponential.learning_rate(5) < 0.1 && exponential.learning_rate(5) > 0.05);
+    }
+
+    #[test]
+    fn cosine_annealing_test() {
+        let cosine = CosineAnnealing::new(1.0, 0.0, 10);
+        assert!(close(1.0, cosine.learning_rate(0)));
+        assert!(close(0.5, cosine.learning_rate(5)));
+        // warm restart
+        assert!(close(1.0, cosine.learning_rate(10)));
+
+        let growing = CosineAnnealing::new(1.0, 0.0, 10).with_period_mult(2);
+        assert!(close(0.5, growing.learning_rate(20)));
+        assert!(close(1.0, growing.learning_rate(30)));
+    }
+
+    #[test]
+    fn warmup_test() {
+        let warmup = LinearWarmup::new(4, StepDecay::new(0.2, 0.5, 10));
+        assert!(close(0.0, warmup.learning_rate(0)));
+        assert!(close(0.1, warmup.learning_rate(2)));
+        assert!(close(0.2, warmup.learning_rate(4)));
+        assert!(close(0.1, warmup.learning_rate(14)));
+    }
+
+    #[test]
+    fn one_cycle_test() {
+        let one_cycle = OneCycle::new(1.0, 101);
+        assert!(close(0.04, one_cycle.learning_rate(0)));
+        assert!(close(1.0, one_cycle.learning_rate(30)));
+        assert!(close(0.04 / 1e4, one_cycle.learning_rate(100)));
+        assert!(one_cycle.learning_rate(15) < one_cycle.learning_rate(30));
+        assert!(one_cycle.learning_rate(60) < one_cycle.learning_rate(30));
+    }
+
+    #[test]
+    fn apply_test() {
+        let mut optimizer = Sgd::new(1.0);
+        StepDecay::new(0.1, 0.5, 1).apply(&mut optimizer, 2);
+        assert!(close(0.025, optimizer.learning_rate()));
+    }
+}
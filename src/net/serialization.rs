@@ -0,0 +1,836 @@
+// saving and loading trained networks, as readable json or as compact binary
+//
+// both formats store the same record: a version, the input size, the loss name with its parameters and the layers,
+// since version 2 the rng state so training resumes exactly where it stopped and since version 3
+// every layer as its kind with the fields of that kind, for dense layers the size, activation name,
+// bias flag and the row-major (fan_in, size) weights, version 4 added activation and batch_norm layers,
+// version 5 layer_norm and rms_norm layers, version 6 the image layers, which also store the
+// (channels, height, width) of their inputs, version 7 the sequence layers with their (channels, length)
+// and version 8 the regularizer of the dense and convolution layers
+use std::fs;
+use std::path::Path;
+
+use ndarray::{Array1, Array2};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use super::activation_functions::Activation;
+use super::block::Block;
+use super::builder::NetworkBuilder;
+use super::conv::{Padding, Shape2, Shape3};
+use super::error::Error;
+use super::layer::Layer;
+use super::loss_functions::loss_from_name;
+use super::network::Network;
+use super::normalization::{BatchNorm, LayerNorm, RmsNorm};
+use super::regularizers::Regularizer;
+
+// bumped whenever the record changes, older versions stay loadable
+pub const FORMAT_VERSION: u32 = 8;
+
+// first bytes of the binary format
+const MAGIC: &[u8; 4] = b"FNET";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SavedLayer<R = Option<Regularizer>> {
+    Dense {
+        size: usize,
+        activation: String,
+        bias: bool,
+        weights: Vec<f32>,
+        biases: Vec<f32>,
+        // missing before version 8, such layers are not regularized
+        #[serde(default)]
+        regularizer: R,
+    },
+    Dropout {
+        rate: f32,
+    },
+    Activation {
+        activation: String,
+    },
+    BatchNorm {
+        momentum: f32,
+        epsilon: f32,
+        scale: Vec<f32>,
+        shift: Vec<f32>,
+        running_mean: Vec<f32>,
+        running_variance: Vec<f32>,
+    },
+    LayerNorm {
+        epsilon: f32,
+        scale: Vec<f32>,
+        shift: Vec<f32>,
+    },
+    RmsNorm {
+        epsilon: f32,
+        scale: Vec<f32>,
+    },
+    Conv2d {
+        input_shape: Shape3,
+        filters: usize,
+        kernel: (usize, usize),
+        stride: (usize, usize),
+        padding: Padding,
+        activation: String,
+        bias: bool,
+        // row-major (channels * kernel height * kernel width, filters)
+        weights: Vec<f32>,
+        biases: Vec<f32>,
+        #[serde(default)]
+        regularizer: R,
+    },
+    MaxPool2d {
+        input_shape: Shape3,
+        pool: (usize, usize),
+        stride: (usize, usize),
+    },
+    AvgPool2d {
+        input_shape: Shape3,
+        pool: (usize, usize),
+        stride: (usize, usize),
+    },
+    Flatten {
+        input_shape: Shape3,
+    },
+    Conv1d {
+        input_shape: Shape2,
+        filters: usize,
+        kernel: usize,
+        stride: usize,
+        dilation: usize,
+        padding: Padding,
+        activation: String,
+        bias: bool,
+        // row-major (channels * kernel, filters)
+        weights: Vec<f32>,
+        biases: Vec<f32>,
+        #[serde(default)]
+        regularizer: R,
+    },
+    GlobalAveragePool1d {
+        input_shape: Shape2,
+    },
+    GlobalMaxPool1d {
+        input_shape: Shape2,
+    },
+}
+
+// R is () for the records before version 8, see without_regularizers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound(deserialize = "R: Deserialize<'de> + Default"))]
+struct SavedNetwork<R = Option<Regularizer>> {
+    version: u32,
+    inputs: usize,
+    loss: String,
+    layers: Vec<SavedLayer<R>>,
+    // missing before version 2, such networks get a fresh rng
+    #[serde(default)]
+    rng: Option<ChaCha8Rng>,
+}
+
+// the layers of versions 1 and 2, which only had dense layers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct LegacyLayer {
+    kind: String,
+    size: usize,
+    activation: String,
+    bias: bool,
+    weights: Vec<f32>,
+    biases: Vec<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct LegacyNetwork {
+    version: u32,
+    inputs: usize,
+    loss: String,
+    layers: Vec<LegacyLayer>,
+    #[serde(default)]
+    rng: Option<ChaCha8Rng>,
+}
+
+impl LegacyNetwork {
+    fn upgrade(self) -> Result<SavedNetwork, Error> {
+        let layers = self
+            .layers
+            .into_iter()
+            .map(|layer| match layer.kind.as_str() {
+                "dense" => Ok(SavedLayer::Dense {
+                    size: layer.size,
+                    activation: layer.activation,
+                    bias: layer.bias,
+                    weights: layer.weights,
+                    biases: layer.biases,
+                    regularizer: None,
+                }),
+                _ => Err(Error::Format(format!("unknown layer kind {:?}", layer.kind))),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(SavedNetwork {
+            version: self.version,
+            inputs: self.inputs,
+            loss: self.loss,
+            layers,
+            rng: self.rng,
+        })
+    }
+}
+
+fn format_error<E: std::fmt::Display>(error: E) -> Error {
+    Error::Format(error.to_string())
+}
+
+fn dense(
+    size: usize,
+    activation: Activation,
+    bias: bool,
+    weights: &Array2<f32>,
+    biases: &Array1<f32>,
+    regularizer: Regularizer,
+) -> SavedLayer {
+    SavedLayer::Dense {
+        size,
+        activation: activation.to_string(),
+        bias,
+        weights: weights.iter().copied().collect(),
+        biases: biases.to_vec(),
+        regularizer: Some(regularizer),
+    }
+}
+
+// versions 3 to 7 had no regularizers, bincode cannot skip the missing fields so such a record is read
+// without them and converted through json, where the regularizers it lacks read as null
+fn without_regularizers(record: &[u8]) -> Result<SavedNetwork, Error> {
+    let record = bincode::deserialize::<SavedNetwork<()>>(record).map_err(format_error)?;
+    serde_json::from_value(serde_json::to_value(record).map_err(format_error)?).map_err(format_error)
+}
+
+// overwrite the parameters of the dense or convolution layer at index layer with the saved ones
+fn restore_dense(
+    weights: &mut Array2<f32>,
+    biases: &mut Array1<f32>,
+    saved: SavedLayer,
+    layer: usize,
+) -> Result<(), Error> {
+    let (SavedLayer::Dense {
+        weights: saved_weights,
+        biases: saved_biases,
+        ..
+    }
+    | SavedLayer::Conv2d {
+        weights: saved_weights,
+        biases: saved_biases,
+        ..
+    }
+    | SavedLayer::Conv1d {
+        weights: saved_weights,
+        biases: saved_biases,
+        ..
+    }) = saved
+    else {
+        return Ok(());
+    };
+    if saved_weights.len() != weights.len() || saved_biases.len() != biases.len() {
+        return Err(Error::ShapeMismatch {
+            layer,
+            expected: weights.len() + biases.len(),
+            found: saved_weights.len() + saved_biases.len(),
+        });
+    }
+    *weights = Array2::from_shape_vec(weights.raw_dim(), saved_weights).unwrap();
+    *biases = Array1::from(saved_biases);
+    Ok(())
+}
+
+fn restore_batch_norm(norm: &mut BatchNorm, saved: SavedLayer, layer: usize) -> Result<(), Error> {
+    let SavedLayer::BatchNorm {
+        momentum,
+        epsilon,
+        scale,
+        shift,
+        running_mean,
+        running_variance,
+    } = saved
+    else {
+        return Ok(());
+    };
+    let lens = [scale.len(), shift.len(), running_mean.len(), running_variance.len()];
+    check_len(norm.len(), &lens, layer)?;
+    norm.momentum = momentum;
+    norm.epsilon = epsilon;
+    norm.scale = Array1::from(scale);
+    norm.shift = Array1::from(shift);
+    norm.running_mean = Array1::from(running_mean);
+    norm.running_variance = Array1::from(running_variance);
+    Ok(())
+}
+
+fn restore_layer_norm(norm: &mut LayerNorm, saved: SavedLayer, layer: usize) -> Result<(), Error> {
+    let SavedLayer::LayerNorm { epsilon, scale, shift } = saved else {
+        return Ok(());
+    };
+    check_len(norm.len(), &[scale.len(), shift.len()], layer)?;
+    norm.epsilon = epsilon;
+    norm.scale = Array1::from(scale);
+    norm.shift = Array1::from(shift);
+    Ok(())
+}
+
+fn restore_rms_norm(norm: &mut RmsNorm, saved: SavedLayer, layer: usize) -> Result<(), Error> {
+    let SavedLayer::RmsNorm { epsilon, scale } = saved else {
+        return Ok(());
+    };
+    check_len(norm.len(), &[scale.len()], layer)?;
+    norm.epsilon = epsilon;
+    norm.scale = Array1::from(scale);
+    Ok(())
+}
+
+// every saved parameter of a normalisation layer has one value per neuron
+fn check_len(size: usize, lens: &[usize], layer: usize) -> Result<(), Error> {
+    match lens.iter().find(|len| **len != size) {
+        Some(found) => Err(Error::ShapeMismatch {
+            layer,
+            expected: size,
+            found: *found,
+        }),
+        None => Ok(()),
+    }
+}
+
+impl SavedNetwork {
+    fn new(network: &Network) -> Self {
+        let mut layers: Vec<SavedLayer> = network
+            .blocks()
+            .iter()
+            .map(|block| match block {
+                Block::Dense(l) => dense(l.len(), l.activation, l.bias, &l.weights, &l.biases, l.regularizer),
+                Block::Dropout(dropout) => SavedLayer::Dropout { rate: dropout.rate },
+                Block::Activation(layer) => SavedLayer::Activation {
+                    activation: layer.activation.to_string(),
+                },
+                Block::BatchNorm(norm) => SavedLayer::BatchNorm {
+                    momentum: norm.momentum,
+                    epsilon: norm.epsilon,
+                    scale: norm.scale.to_vec(),
+                    shift: norm.shift.to_vec(),
+                    running_mean: norm.running_mean.to_vec(),
+                    running_variance: norm.running_variance.to_vec(),
+                },
+                Block::LayerNorm(norm) => SavedLayer::LayerNorm {
+                    epsilon: norm.epsilon,
+                    scale: norm.scale.to_vec(),
+                    shift: norm.shift.to_vec(),
+                },
+                Block::RmsNorm(norm) => SavedLayer::RmsNorm {
+                    epsilon: norm.epsilon,
+                    scale: norm.scale.to_vec(),
+                },
+                Block::Conv2D(conv) => SavedLayer::Conv2d {
+                    input_shape: conv.input_shape,
+                    filters: conv.filters(),
+                    kernel: conv.kernel,
+                    stride: conv.stride,
+                    padding: conv.padding,
+                    activation: conv.activation.to_string(),
+                    bias: conv.bias,
+                    weights: conv.weights.iter().copied().collect(),
+                    biases: conv.biases.to_vec(),
+                    regularizer: Some(conv.regularizer),
+                },
+                Block::MaxPool2D(pool) => SavedLayer::MaxPool2d {
+                    input_shape: pool.window.input_shape,
+                    pool: pool.window.pool,
+                    stride: pool.window.stride,
+                },
+                Block::AvgPool2D(pool) => SavedLayer::AvgPool2d {
+                    input_shape: pool.window.input_shape,
+                    pool: pool.window.pool,
+                    stride: pool.window.stride,
+                },
+                Block::Flatten(flatten) => SavedLayer::Flatten {
+                    input_shape: flatten.input_shape,
+                },
+                Block::Conv1D(conv) => SavedLayer::Conv1d {
+                    input_shape: conv.input_shape,
+                    filters: conv.filters(),
+                    kernel: conv.kernel,
+                    stride: conv.stride,
+                    dilation: conv.dilation,
+                    padding: conv.padding,
+                    activation: conv.activation.to_string(),
+                    bias: conv.bias,
+                    weights: conv.weights.iter().copied().collect(),
+                    biases: conv.biases.to_vec(),
+                    regularizer: Some(conv.regularizer),
+                },
+                Block::GlobalAveragePool1D(pool) => SavedLayer::GlobalAveragePool1d {
+                    input_shape: pool.input_shape,
+                },
+                Block::GlobalMaxPool1D(pool) => SavedLayer::GlobalMaxPool1d {
+                    input_shape: pool.input_shape,
+                },
+            })
+            .collect();
+        let output = network.output_layer();
+        layers.push(dense(
+            output.len(),
+            output.activation,
+            output.bias,
+            &output.weights,
+            &output.biases,
+            output.regularizer,
+        ));
+        Self {
+            version: FORMAT_VERSION,
+            inputs: network.input_layer().len(),
+            loss: network.loss().describe(),
+            layers,
+            rng: Some(network.rng_state().clone()),
+        }
+    }
+
+    fn into_network(self) -> Result<Network, Error> {
+        if self.version > FORMAT_VERSION {
+            return Err(Error::Format(format!(
+                "format version {} is newer than the supported version {}",
+                self.version, FORMAT_VERSION
+            )));
+        }
+
+        let mut builder = NetworkBuilder::new(self.inputs as u32);
+        for layer in &self.layers {
+            builder = match layer {
+                SavedLayer::Dense {
+                    size,
+                    activation,
+                    bias,
+                    regularizer,
+                    ..
+                } => {
+                    let activation = activation.parse::<Activation>().map_err(Error::Format)?;
+                    builder
+                        .bias(*bias)
+                        .regularizer(regularizer.unwrap_or_default())
+                        .dense(*size as u32, activation)
+                }
+                SavedLayer::Dropout { rate } => builder.dropout(*rate),
+                SavedLayer::Activation { activation } => {
+                    builder.activation(activation.parse::<Activation>().map_err(Error::Format)?)
+                }
+                SavedLayer::BatchNorm { .. } => builder.batch_norm(),
+                SavedLayer::LayerNorm { .. } => builder.layer_norm(),
+                SavedLayer::RmsNorm { .. } => builder.rms_norm(),
+                // the image layers bring the shape of their inputs
+                SavedLayer::Conv2d {
+                    input_shape: (channels, height, width),
+                    filters,
+                    kernel,
+                    stride,
+                    padding,
+                    activation,
+                    bias,
+                    regularizer,
+                    ..
+                } => {
+                    let activation = activation.parse::<Activation>().map_err(Error::Format)?;
+                    builder
+                        .reshape(*channels, *height, *width)
+                        .bias(*bias)
+                        .regularizer(regularizer.unwrap_or_default())
+                        .conv2d(*filters, *kernel, *stride, *padding, activation)
+                }
+                SavedLayer::MaxPool2d {
+                    input_shape: (channels, height, width),
+                    pool,
+                    stride,
+                } => builder.reshape(*channels, *height, *width).max_pool2d(*pool, *stride),
+                SavedLayer::AvgPool2d {
+                    input_shape: (channels, height, width),
+                    pool,
+                    stride,
+                } => builder.reshape(*channels, *height, *width).avg_pool2d(*pool, *stride),
+                SavedLayer::Flatten {
+                    input_shape: (channels, height, width),
+                } => builder.reshape(*channels, *height, *width).flatten(),
+                SavedLayer::Conv1d {
+                    input_shape: (channels, length),
+                    filters,
+                    kernel,
+                    stride,
+                    dilation,
+                    padding,
+                    activation,
+                    bias,
+                    regularizer,
+                    ..
+                } => {
+                    let activation = activation.parse::<Activation>().map_err(Error::Format)?;
+                    builder
+                        .reshape1d(*channels, *length)
+                        .bias(*bias)
+                        .regularizer(regularizer.unwrap_or_default())
+                        .conv1d(*filters, *kernel, *stride, *dilation, *padding, activation)
+                }
+                SavedLayer::GlobalAveragePool1d {
+                    input_shape: (channels, length),
+                } => builder.reshape1d(*channels, *length).global_average_pool1d(),
+                SavedLayer::GlobalMaxPool1d {
+                    input_shape: (channels, length),
+                } => builder.reshape1d(*channels, *length).global_max_pool1d(),
+            };
+        }
+        let mut network = builder.build()?;
+
+        let mut layers = self.layers;
+        let output_index = layers.len();
+        if let Some(output) = layers.pop() {
+            let output_layer = network.output_layer_mut();
+            let core = &mut output_layer.core;
+            restore_dense(&mut core.weights, &mut core.biases, output, output_index)?;
+        }
+        for (n, (layer, block)) in layers.into_iter().zip(network.blocks_mut()).enumerate() {
+            match block {
+                Block::Dense(dense) => restore_dense(&mut dense.core.weights, &mut dense.core.biases, layer, n + 1)?,
+                Block::Conv2D(conv) => restore_dense(&mut conv.weights, &mut conv.biases, layer, n + 1)?,
+                Block::Conv1D(conv) => restore_dense(&mut conv.weights, &mut conv.biases, layer, n + 1)?,
+                Block::BatchNorm(norm) => restore_batch_norm(norm, layer, n + 1)?,
+                Block::LayerNorm(norm) => restore_layer_norm(norm, layer, n + 1)?,
+                Block::RmsNorm(norm) => restore_rms_norm(norm, layer, n + 1)?,
+                _ => {}
+            }
+        }
+
+        // only the built-in losses can be restored by name
+        let loss = loss_from_name(&self.loss).ok_or_else(|| Error::Format(format!("unknown loss: {}", self.loss)))?;
+        network.set_shared_loss(loss);
+        if let Some(rng) = self.rng {
+            network.set_rng(rng);
+        }
+        network.validate()?;
+        Ok(network)
+    }
+}
+
+impl Network {
+    // writes json if the path ends in .json and the binary format otherwise
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let bytes = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => self.to_json()?.into_bytes(),
+            _ => self.to_bytes()?,
+        };
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    // reads either format, whichever the file contains
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let bytes = fs::read(path)?;
+        if bytes.starts_with(MAGIC) {
+            Self::from_bytes(&bytes)
+        } else {
+            Self::from_json(std::str::from_utf8(&bytes).map_err(format_error)?)
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(&SavedNetwork::new(self)).map_err(format_error)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let value: serde_json::Value = serde_json::from_str(json).map_err(format_error)?;
+        let record = match value.get("version").and_then(|v| v.as_u64()) {
+            Some(version) if version < 3 => serde_json::from_value::<LegacyNetwork>(value)
+                .map_err(format_error)?
+                .upgrade()?,
+            _ => serde_json::from_value::<SavedNetwork>(value).map_err(format_error)?,
+        };
+        record.into_network()
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = MAGIC.to_vec();
+        bincode::serialize_into(&mut bytes, &SavedNetwork::new(self)).map_err(format_error)?;
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let record = bytes
+            .strip_prefix(MAGIC)
+            .ok_or_else(|| Error::Format("missing magic number".to_string()))?;
+        // the version is the leading little-endian u32
+        let version = match record.get(..4) {
+            Some(bytes) => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            None => return Err(Error::Format("missing format version".to_string())),
+        };
+        let record = match version {
+            // bincode has no optional fields, a version 1 record simply ends before the rng
+            // and a single 0 byte encodes None
+            1 => {
+                let mut record = record.to_vec();
+                record.push(0);
+                bincode::deserialize::<LegacyNetwork>(&record).map_err(format_error)?.upgrade()?
+            }
+            2 => bincode::deserialize::<LegacyNetwork>(record).map_err(format_error)?.upgrade()?,
+            3..=7 => without_regularizers(record)?,
+            _ => bincode::deserialize::<SavedNetwork>(record).map_err(format_error)?,
+        };
+        record.into_network()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+    use rand::RngCore;
+
+    use crate::net::loss_functions::Huber;
+    use crate::net::optimizers::Sgd;
+
+    use super::*;
+
+    fn network() -> Network {
+        let mut network = NetworkBuilder::new(2)
+            .dense(3, Activation::Identity)
+            .batch_norm()
+            .activation(Activation::LeakyRelu(0.1))
+            .dropout(0.5)
+            .layer_norm()
+            .dense(4, Activation::Tanh)
+            .rms_norm()
+            .bias(false)
+            .dense(2, Activation::Softmax)
+            .build()
+            .unwrap();
+        network.set_loss(Huber::new(0.5));
+        // move every parameter and the running statistics away from their initial values
+        network.forward_batch(&array![[1.0, 2.0], [-1.0, 0.5], [3.0, 0.0]]).unwrap();
+        let targets = array![[1.0, 0.0], [0.0, 1.0], [1.0, 0.0]];
+        network.backward_batch(&targets, &mut Sgd::new(0.5)).unwrap();
+        network
+    }
+
+    // a record as versions 1 and 2 wrote it, those only had dense layers
+    fn legacy(network: &Network, version: u32) -> LegacyNetwork {
+        let layers = SavedNetwork::new(network)
+            .layers
+            .into_iter()
+            .map(|layer| match layer {
+                SavedLayer::Dense {
+                    size,
+                    activation,
+                    bias,
+                    weights,
+                    biases,
+                    ..
+                } => LegacyLayer {
+                    kind: "dense".to_string(),
+                    size,
+                    activation,
+                    bias,
+                    weights,
+                    biases,
+                },
+                _ => unreachable!(),
+            })
+            .collect();
+        LegacyNetwork {
+            version,
+            inputs: network.input_layer().len(),
+            loss: network.loss().describe(),
+            layers,
+            rng: (version > 1).then(|| network.rng_state().clone()),
+        }
+    }
+
+    fn assert_same(a: &mut Network, b: &mut Network) {
+        let inputs = array![[0.5, -1.0], [2.0, 0.25]];
+        a.eval();
+        b.eval();
+        assert_eq!(a.forward_batch(&inputs).unwrap(), b.forward_batch(&inputs).unwrap());
+        a.train();
+        b.train();
+        assert_eq!(a.forward_batch(&inputs).unwrap(), b.forward_batch(&inputs).unwrap());
+        assert_eq!(a.loss().describe(), b.loss().describe());
+        assert_eq!(a.hidden_layers()[0].activation, b.hidden_layers()[0].activation);
+        assert_eq!(a.output_layer().bias, b.output_layer().bias);
+    }
+
+    #[test]
+    fn round_trip_test() {
+        let mut network = network();
+
+        let mut from_json = Network::from_json(&network.to_json().unwrap()).unwrap();
+        assert_same(&mut network, &mut from_json);
+
+        let mut from_bytes = Network::from_bytes(&network.to_bytes().unwrap()).unwrap();
+        assert_same(&mut network, &mut from_bytes);
+    }
+
+    #[test]
+    fn save_load_test() {
+        let mut network = network();
+        let dir = std::env::temp_dir();
+        for extension in ["json", "bin"] {
+            let path = dir.join(format!("firstnet-{}-network.{}", std::process::id(), extension));
+            network.save(&path).unwrap();
+            let loaded = Network::load(&path);
+            fs::remove_file(&path).unwrap();
+            assert_same(&mut network, &mut loaded.unwrap());
+        }
+    }
+
+    #[test]
+    fn rng_test() {
+        let mut network = network();
+        let mut loaded = Network::from_bytes(&network.to_bytes().unwrap()).unwrap();
+        assert_eq!(network.rng().next_u64(), loaded.rng().next_u64());
+        let mut loaded = Network::from_json(&network.to_json().unwrap()).unwrap();
+        assert_eq!(network.rng().next_u64(), loaded.rng().next_u64());
+    }
+
+    #[test]
+    fn conv_test() {
+        let mut network = NetworkBuilder::new(18)
+            .reshape(2, 3, 3)
+            .conv2d(3, (2, 2), (1, 1), Padding::Same, Activation::Relu)
+            .max_pool2d((2, 2), (1, 1))
+            .bias(false)
+            .regularizer(Regularizer::default().l2(1e-3))
+            .conv2d(2, (1, 2), (1, 1), Padding::Zeros(1), Activation::Tanh)
+            .avg_pool2d((2, 2), (2, 2))
+            .flatten()
+            .bias(true)
+            .dense(2, Activation::Softmax)
+            .build()
+            .unwrap();
+        let inputs = Array2::from_shape_fn((3, 18), |(i, j)| ((i * 18 + j) as f32).cos());
+        network.forward_batch(&inputs).unwrap();
+        network.backward_batch(&array![[1.0, 0.0], [0.0, 1.0], [1.0, 0.0]], &mut Sgd::new(0.5)).unwrap();
+
+        let json = network.to_json().unwrap();
+        assert!(json.contains("\"zeros\": 1"), "{}", json);
+        for mut loaded in [Network::from_json(&json).unwrap(), Network::from_bytes(&network.to_bytes().unwrap()).unwrap()] {
+            assert_eq!(network.forward_batch(&inputs).unwrap(), loaded.forward_batch(&inputs).unwrap());
+            let Block::Conv2D(conv) = &loaded.blocks()[2] else { unreachable!() };
+            assert!(!conv.bias);
+            assert_eq!(Regularizer::default().l2(1e-3), conv.regularizer);
+        }
+    }
+
+    #[test]
+    fn conv1d_test() {
+        let mut network = NetworkBuilder::new(12)
+            .reshape1d(2, 6)
+            .regularizer(Regularizer::default().max_norm(2.0))
+            .conv1d(3, 2, 1, 2, Padding::Causal, Activation::Relu)
+            .global_average_pool1d()
+            .dense(2, Activation::Softmax)
+            .build()
+            .unwrap();
+        let inputs = Array2::from_shape_fn((3, 12), |(i, j)| ((i * 12 + j) as f32).sin());
+        network.forward_batch(&inputs).unwrap();
+        network.backward_batch(&array![[1.0, 0.0], [0.0, 1.0], [1.0, 0.0]], &mut Sgd::new(0.5)).unwrap();
+        let json = network.to_json().unwrap();
+        let mut max = Network::from_json(&json.replace("global_average_pool1d", "global_max_pool1d")).unwrap();
+        assert!(matches!(max.blocks()[1], Block::GlobalMaxPool1D(_)));
+        max.forward_batch(&inputs).unwrap();
+
+        for mut loaded in [Network::from_json(&json).unwrap(), Network::from_bytes(&network.to_bytes().unwrap()).unwrap()] {
+            assert_eq!(network.forward_batch(&inputs).unwrap(), loaded.forward_batch(&inputs).unwrap());
+            let Block::Conv1D(conv) = &loaded.blocks()[0] else { unreachable!() };
+            assert_eq!((2, Padding::Causal), (conv.dilation, conv.padding));
+            assert_eq!(Some(2.0), conv.regularizer.max_norm);
+        }
+    }
+
+    #[test]
+    fn regularizer_test() {
+        let regularizer = Regularizer::default().l1(1e-3).l2(1e-4).weight_decay(0.01).max_norm(3.0).include_bias(true);
+        let mut network = NetworkBuilder::new(2)
+            .regularizer(regularizer)
+            .dense(3, Activation::Tanh)
+            .regularizer(Regularizer::default().non_negative(true))
+            .dense(2, Activation::Sigmoid)
+            .build()
+            .unwrap();
+        let json = network.to_json().unwrap();
+        for loaded in [Network::from_json(&json).unwrap(), Network::from_bytes(&network.to_bytes().unwrap()).unwrap()] {
+            assert_eq!(regularizer, loaded.hidden_layers()[0].regularizer);
+            assert!(loaded.output_layer().regularizer.non_negative);
+        }
+
+        // a version 7 record, without the regularizers, loads with none
+        let mut value = serde_json::to_value(SavedNetwork::new(&network)).unwrap();
+        value["version"] = 7.into();
+        for layer in value["layers"].as_array_mut().unwrap() {
+            for fields in layer.as_object_mut().unwrap().values_mut() {
+                fields.as_object_mut().unwrap().remove("regularizer");
+            }
+        }
+        let mut bytes = MAGIC.to_vec();
+        let old = serde_json::from_value::<SavedNetwork<()>>(value.clone()).unwrap();
+        bincode::serialize_into(&mut bytes, &old).unwrap();
+        for mut loaded in [Network::from_json(&value.to_string()).unwrap(), Network::from_bytes(&bytes).unwrap()] {
+            assert_eq!(Regularizer::default(), loaded.hidden_layers()[0].regularizer);
+            assert_eq!(Regularizer::default(), loaded.output_layer().regularizer);
+            assert_same(&mut network, &mut loaded);
+        }
+    }
+
+    #[test]
+    fn legacy_test() {
+        let mut network = NetworkBuilder::new(2)
+            .dense(3, Activation::Tanh)
+            .bias(false)
+            .dense(2, Activation::Sigmoid)
+            .build()
+            .unwrap();
+
+        let mut bytes = MAGIC.to_vec();
+        bincode::serialize_into(&mut bytes, &legacy(&network, 1)).unwrap();
+        // drop the None of the rng, version 1 did not have it
+        assert_eq!(Some(0), bytes.pop());
+        assert_same(&mut network, &mut Network::from_bytes(&bytes).unwrap());
+
+        let json = serde_json::to_string(&legacy(&network, 1)).unwrap().replace(",\"rng\":null", "");
+        assert_same(&mut network, &mut Network::from_json(&json).unwrap());
+
+        let mut bytes = MAGIC.to_vec();
+        bincode::serialize_into(&mut bytes, &legacy(&network, 2)).unwrap();
+        let mut loaded = Network::from_bytes(&bytes).unwrap();
+        assert_eq!(network.rng().next_u64(), loaded.rng().next_u64());
+        assert_same(&mut network, &mut loaded);
+
+        let json = serde_json::to_string(&legacy(&network, 2)).unwrap();
+        assert_same(&mut network, &mut Network::from_json(&json).unwrap());
+    }
+
+    #[test]
+    fn format_error_test() {
+        let json = network().to_json().unwrap();
+        let newer = json.replace(&format!("\"version\": {}", FORMAT_VERSION), "\"version\": 99");
+        assert!(matches!(Network::from_json(&newer), Err(Error::Format(_))));
+
+        let unknown = json.replace("leaky_relu(0.1)", "swoosh");
+        assert!(matches!(Network::from_json(&unknown), Err(Error::Format(_))));
+
+        for loss in ["hubber(0.5)", "huber(0,5)", "huber(0.5"] {
+            let unknown = json.replace("huber(0.5)", loss);
+            assert!(matches!(Network::from_json(&unknown), Err(Error::Format(_))), "{}", loss);
+        }
+        let path = std::env::temp_dir().join(format!("firstnet-{}-loss.json", std::process::id()));
+        fs::write(&path, json.replace("huber(0.5)", "huber(x)")).unwrap();
+        let loaded = Network::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(loaded, Err(Error::Format(_))));
+
+        let mut bytes = network().to_bytes().unwrap();
+        bytes.truncate(bytes.len() - 4);
+        assert!(matches!(Network::from_bytes(&bytes), Err(Error::Format(_))));
+        assert!(matches!(Network::from_bytes(b"{}"), Err(Error::Format(_))));
+    }
+}
@@ -0,0 +1,676 @@
+use std::fmt;
+
+use rand::seq::SliceRandom;
+
+use super::callbacks::{Callback, Control, LrScheduler, Metrics};
+use super::dataset::Dataset;
+use super::error::Error;
+use super::network::Network;
+use super::optimizers::Optimizer;
+use super::schedules::LrSchedule;
+
+// stop when the monitored loss has not improved by more than min_delta for patience epochs
+#[derive(Debug, Clone, Copy)]
+pub struct EarlyStopping {
+    pub patience: usize,
+    pub min_delta: f32,
+    // put back the weights of the best epoch once training ends
+    pub restore_best_weights: bool,
+}
+
+impl EarlyStopping {
+    pub fn new(patience: usize) -> Self {
+        Self {
+            patience,
+            min_delta: 0.0,
+            restore_best_weights: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TrainConfig {
+    pub epochs: usize,
+    pub batch_size: usize,
+    pub shuffle: bool,
+    // fraction of the dataset held out for validation, taken from the end before shuffling
+    pub validation_split: f32,
+    // monitors the validation loss, or the training loss without a validation set
+    pub early_stopping: Option<EarlyStopping>,
+}
+
+impl Default for TrainConfig {
+    fn default() -> Self {
+        Self {
+            epochs: 10,
+            batch_size: 32,
+            shuffle: true,
+            validation_split: 0.0,
+            early_stopping: None,
+        }
+    }
+}
+
+// losses per epoch, validation_loss stays empty without a validation set
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct History {
+    pub train_loss: Vec<f32>,
+    pub validation_loss: Vec<f32>,
+    pub best_epoch: Option<usize>,
+    pub stopped_early: bool,
+}
+
+impl History {
+    pub fn epochs(&self) -> usize {
+        self.train_loss.len()
+    }
+}
+
+pub struct Trainer {
+    optimizer: Box<dyn Optimizer>,
+    callbacks: Vec<Box<dyn Callback>>,
+    seed: Option<u64>,
+}
+
+impl Trainer {
+    pub fn new<O: Optimizer + 'static>(optimizer: O) -> Self {
+        Self {
+            optimizer: Box::new(optimizer),
+            callbacks: vec![],
+            seed: None,
+        }
+    }
+
+    // reseed the rng of the network at the start of every fit,
+    // without a seed training continues from the current rng state of the network
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    // callbacks fire in the order they were added
+    pub fn with_callback<C: Callback + 'static>(mut self, callback: C) -> Self {
+        self.callbacks.push(Box::new(callback));
+        self
+    }
+
+    // query the schedule with the epoch index before every epoch
+    pub fn with_schedule<S: LrSchedule + 'static>(self, schedule: S) -> Self {
+        self.with_callback(LrScheduler::per_epoch(schedule))
+    }
+
+    // query the schedule with the global batch index before every batch
+    pub fn with_batch_schedule<S: LrSchedule + 'static>(self, schedule: S) -> Self {
+        self.with_callback(LrScheduler::per_batch(schedule))
+    }
+
+    pub fn optimizer(&self) -> &dyn Optimizer {
+        self.optimizer.as_ref()
+    }
+
+    pub fn fit(&mut self, network: &mut Network, dataset: &Dataset, config: TrainConfig) -> Result<History, Error> {
+        if config.validation_split > 0.0 {
+            let (train, validation) = dataset.split(config.validation_split);
+            self.fit_with_validation(network, &train, Some(&validation), config)
+        } else {
+            self.fit_with_validation(network, dataset, None, config)
+        }
+    }
+
+    pub fn fit_with_validation(
+        &mut self,
+        network: &mut Network,
+        train: &Dataset,
+        validation: Option<&Dataset>,
+        config: TrainConfig,
+    ) -> Result<History, Error> {
+        if train.is_empty() {
+            return Err(Error::EmptyBatch);
+        }
+        let validation = validation.filter(|v| !v.is_empty());
+        if let Some(seed) = self.seed {
+            network.set_seed(seed);
+        }
+        // the batches run in training mode, evaluate switches to inference mode for the validation loss,
+        // the mode of the caller comes back on every exit, errors included
+        let training = network.is_training();
+        network.train();
+        let history = self.run(network, train, validation, config);
+        if !training {
+            network.eval();
+        }
+        let history = history?;
+        for callback in self.callbacks.iter_mut() {
+            callback.on_train_end(network, &history);
+        }
+        Ok(history)
+    }
+
+    // the epochs of fit_with_validation, the network is in training mode
+    fn run(
+        &mut self,
+        network: &mut Network,
+        train: &Dataset,
+        validation: Option<&Dataset>,
+        config: TrainConfig,
+    ) -> Result<History, Error> {
+        let batch_size = config.batch_size.clamp(1, train.len());
+
+        let mut history = History::default();
+        let mut best_loss: Option<f32> = None;
+        let mut best_network: Option<Network> = None;
+        let mut epochs_without_improvement = 0;
+        let mut indices: Vec<usize> = (0..train.len()).collect();
+        let mut step = 0;
+
+        'epochs: for epoch in 0..config.epochs {
+            // every epoch starts from the row order, so its permutation depends on the rng state alone
+            if config.shuffle {
+                indices.sort_unstable();
+                indices.shuffle(network.rng());
+            }
+
+            let mut epoch_loss = 0.0;
+            for (batch, batch_indices) in indices.chunks(batch_size).enumerate() {
+                let mut metrics = Metrics {
+                    epoch,
+                    batch,
+                    step,
+                    ..Metrics::default()
+                };
+                for callback in self.callbacks.iter_mut() {
+                    callback.on_batch_start(network, self.optimizer.as_mut(), &metrics);
+                }
+
+                let batch = train.select(batch_indices);
+                network.forward_batch(&batch.features)?;
+                let loss = network.backward_batch(&batch.targets, self.optimizer.as_mut())?;
+                epoch_loss += loss * batch_indices.len() as f32;
+                step += 1;
+
+                metrics.loss = loss;
+                metrics.learning_rate = self.optimizer.learning_rate();
+                if notify(&mut self.callbacks, |c| c.on_batch_end(network, &metrics)) == Control::Stop {
+                    history.stopped_early = true;
+                    break 'epochs;
+                }
+            }
+            let train_loss = epoch_loss / train.len() as f32;
+            history.train_loss.push(train_loss);
+
+            let validation_loss = match validation {
+                Some(validation) => Some(evaluate(network, validation)?),
+                None => None,
+            };
+            history.validation_loss.extend(validation_loss);
+
+            let metrics = Metrics {
+                epoch,
+                batch: 0,
+                step,
+                loss: train_loss,
+                validation_loss,
+                learning_rate: self.optimizer.learning_rate(),
+            };
+            let optimizer = self.optimizer.as_mut();
+            if notify(&mut self.callbacks, |c| c.on_epoch_end(network, optimizer, &metrics)) == Control::Stop {
+                history.stopped_early = true;
+                break;
+            }
+
+            let Some(early_stopping) = config.early_stopping else {
+                continue;
+            };
+            let monitored = validation_loss.unwrap_or(train_loss);
+            let improved = match best_loss {
+                Some(best_loss) => monitored < best_loss - early_stopping.min_delta,
+                None => true,
+            };
+            if improved {
+                history.best_epoch = Some(epoch);
+                best_loss = Some(monitored);
+                epochs_without_improvement = 0;
+                if early_stopping.restore_best_weights {
+                    best_network = Some(network.clone());
+                }
+            } else {
+                epochs_without_improvement += 1;
+                if epochs_without_improvement >= early_stopping.patience {
+                    history.stopped_early = true;
+                    break;
+                }
+            }
+        }
+
+        // only the weights and running statistics go back, the rng keeps its state
+        // so later epochs do not repeat the shuffles and dropout masks already drawn
+        if let Some(best_network) = best_network {
+            let rng = network.rng_state().clone();
+            *network = best_network;
+            network.set_rng(rng);
+        }
+        Ok(history)
+    }
+}
+
+// every callback sees the event, training stops if any of them asks to
+fn notify<F: FnMut(&mut dyn Callback) -> Control>(callbacks: &mut [Box<dyn Callback>], mut event: F) -> Control {
+    let mut control = Control::Continue;
+    for callback in callbacks.iter_mut() {
+        if event(callback.as_mut()) == Control::Stop {
+            control = Control::Stop;
+        }
+    }
+    control
+}
+
+impl fmt::Debug for Trainer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Trainer")
+            .field("optimizer", &self.optimizer)
+            .field("callbacks", &self.callbacks.len())
+            .field("seed", &self.seed)
+            .finish()
+    }
+}
+
+// loss of the network over a whole dataset in inference mode, the mode is restored afterwards
+pub fn evaluate(network: &mut Network, dataset: &Dataset) -> Result<f32, Error> {
+    let training = network.is_training();
+    network.eval();
+    let loss = network
+        .forward_batch(&dataset.features)
+        .and_then(|_| network.calc_batch_error(&dataset.targets));
+    if training {
+        network.train();
+    }
+    loss
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use ndarray::{array, Array2};
+    use rand::RngCore;
+
+    use crate::net::activation_functions::Activation;
+    use crate::net::block::Block;
+    use crate::net::builder::NetworkBuilder;
+    use crate::net::callbacks::OnEpochEnd;
+    use crate::net::conv::Padding;
+    use crate::net::optimizers::{Adam, Sgd};
+    use crate::net::regularizers::Regularizer;
+    use crate::net::schedules::StepDecay;
+
+    use super::*;
+
+    // y = 2x - 1
+    fn line() -> Dataset {
+        let features = Array2::from_shape_fn((20, 1), |(i, _)| i as f32 / 20.0);
+        let targets = features.mapv(|x| 2.0 * x - 1.0);
+        Dataset::new(features, targets).unwrap()
+    }
+
+    #[test]
+    fn fit_test() {
+        let mut net = NetworkBuilder::new(1).dense(1, Activation::Identity).build().unwrap();
+        let mut trainer = Trainer::new(Sgd::new(0.5));
+        let config = TrainConfig {
+            epochs: 200,
+            batch_size: 5,
+            ..TrainConfig::default()
+        };
+
+        let history = trainer.fit(&mut net, &line(), config).unwrap();
+        assert_eq!(200, history.epochs());
+        assert!(history.validation_loss.is_empty());
+        assert!(history.train_loss[199] < 1e-4);
+        assert!((net.output_layer().weights[[0, 0]] - 2.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn validation_split_test() {
+        let mut net = NetworkBuilder::new(1).dense(1, Activation::Identity).build().unwrap();
+        let config = TrainConfig {
+            epochs: 3,
+            validation_split: 0.25,
+            ..TrainConfig::default()
+        };
+        let history = Trainer::new(Sgd::new(0.1)).fit(&mut net, &line(), config).unwrap();
+        assert_eq!(3, history.validation_loss.len());
+    }
+
+    #[test]
+    fn early_stopping_test() {
+        let mut net = NetworkBuilder::new(1).seed(3).dense(1, Activation::Identity).build().unwrap();
+        let mut kept = net.clone();
+        // the validation set contradicts the training set, so it only gets worse
+        let validation = Dataset::new(array![[0.0], [1.0]], array![[5.0], [-5.0]]).unwrap();
+        let config = TrainConfig {
+            epochs: 100,
+            early_stopping: Some(EarlyStopping::new(3)),
+            ..TrainConfig::default()
+        };
+
+        let mut trainer = Trainer::new(Adam::new(0.05));
+        let history = trainer
+            .fit_with_validation(&mut net, &line(), Some(&validation), config)
+            .unwrap();
+        assert!(history.stopped_early);
+        let best_epoch = history.best_epoch.unwrap();
+        assert_eq!(best_epoch + 4, history.epochs());
+
+        // the restored weights reproduce the best validation loss
+        let best_loss = history.validation_loss[best_epoch];
+        assert!((evaluate(&mut net, &validation).unwrap() - best_loss).abs() < 1e-6);
+
+        // but the rng is where the last epoch left it, as without restoring
+        let config = TrainConfig {
+            epochs: 100,
+            early_stopping: Some(EarlyStopping {
+                restore_best_weights: false,
+                ..EarlyStopping::new(3)
+            }),
+            ..TrainConfig::default()
+        };
+        Trainer::new(Adam::new(0.05))
+            .fit_with_validation(&mut kept, &line(), Some(&validation), config)
+            .unwrap();
+        assert_eq!(kept.rng().next_u64(), net.rng().next_u64());
+    }
+
+    #[test]
+    fn schedule_test() {
+        let mut net = NetworkBuilder::new(1).dense(1, Activation::Identity).build().unwrap();
+        let mut trainer = Trainer::new(Sgd::new(1.0)).with_schedule(StepDecay::new(0.1, 0.5, 1));
+        let config = TrainConfig {
+            epochs: 3,
+            ..TrainConfig::default()
+        };
+        trainer.fit(&mut net, &line(), config).unwrap();
+        assert!((trainer.optimizer().learning_rate() - 0.025).abs() < 1e-6);
+    }
+
+    #[test]
+    fn empty_dataset_test() {
+        let mut net = NetworkBuilder::new(1).dense(1, Activation::Identity).build().unwrap();
+        let empty = Dataset::new(Array2::zeros((0, 1)), Array2::zeros((0, 1))).unwrap();
+        assert_eq!(
+            Err(Error::EmptyBatch),
+            Trainer::new(Sgd::new(0.1)).fit(&mut net, &empty, TrainConfig::default())
+        );
+    }
+
+    // counts how often every hook fired, shared so the test can read it after training
+    #[derive(Debug, Default)]
+    struct Counter {
+        batch_starts: usize,
+        batch_ends: usize,
+        epoch_ends: usize,
+        train_ends: usize,
+    }
+
+    impl Callback for Rc<RefCell<Counter>> {
+        fn on_batch_start(&mut self, _network: &Network, _optimizer: &mut dyn Optimizer, metrics: &Metrics) {
+            let mut counter = self.borrow_mut();
+            assert_eq!(counter.batch_starts, metrics.step);
+            counter.batch_starts += 1;
+        }
+
+        fn on_batch_end(&mut self, _network: &Network, metrics: &Metrics) -> Control {
+            assert!(metrics.loss.is_finite());
+            self.borrow_mut().batch_ends += 1;
+            Control::Continue
+        }
+
+        fn on_epoch_end(&mut self, _network: &Network, _optimizer: &mut dyn Optimizer, metrics: &Metrics) -> Control {
+            let mut counter = self.borrow_mut();
+            assert_eq!(counter.epoch_ends, metrics.epoch);
+            assert!(metrics.validation_loss.is_some());
+            counter.epoch_ends += 1;
+            Control::Continue
+        }
+
+        fn on_train_end(&mut self, _network: &Network, history: &History) {
+            let mut counter = self.borrow_mut();
+            assert_eq!(counter.epoch_ends, history.epochs());
+            counter.train_ends += 1;
+        }
+    }
+
+    #[test]
+    fn callback_test() {
+        let mut net = NetworkBuilder::new(1).dense(1, Activation::Identity).build().unwrap();
+        let config = TrainConfig {
+            epochs: 3,
+            batch_size: 5,
+            validation_split: 0.25,
+            ..TrainConfig::default()
+        };
+        let counter = Rc::new(RefCell::new(Counter::default()));
+        let mut trainer = Trainer::new(Sgd::new(0.1)).with_callback(counter.clone());
+        trainer.fit(&mut net, &line(), config).unwrap();
+
+        let counter = counter.borrow();
+        // 15 training samples in batches of 5
+        assert_eq!(9, counter.batch_starts);
+        assert_eq!(9, counter.batch_ends);
+        assert_eq!(3, counter.epoch_ends);
+        assert_eq!(1, counter.train_ends);
+    }
+
+    #[test]
+    fn callback_stop_test() {
+        let mut net = NetworkBuilder::new(1).dense(1, Activation::Identity).build().unwrap();
+        let stop = OnEpochEnd(|_: &Network, metrics: &Metrics| {
+            if metrics.loss < 0.01 {
+                Control::Stop
+            } else {
+                Control::Continue
+            }
+        });
+        let config = TrainConfig {
+            epochs: 1000,
+            batch_size: 5,
+            ..TrainConfig::default()
+        };
+        let history = Trainer::new(Sgd::new(0.5))
+            .with_callback(stop)
+            .fit(&mut net, &line(), config)
+            .unwrap();
+        assert!(history.stopped_early);
+        assert!(history.epochs() < 1000);
+        assert!(history.train_loss[history.epochs() - 1] < 0.01);
+    }
+
+    #[test]
+    fn seed_test() {
+        let train = |epochs: usize, network: &mut Network| {
+            let config = TrainConfig {
+                epochs,
+                batch_size: 3,
+                ..TrainConfig::default()
+            };
+            Trainer::new(Sgd::new(0.1)).fit(network, &line(), config).unwrap()
+        };
+        let builder = NetworkBuilder::new(1).seed(42).dense(4, Activation::Tanh).dense(1, Activation::Identity);
+
+        let mut a = builder.clone().build().unwrap();
+        let mut b = builder.clone().build().unwrap();
+        assert_eq!(train(4, &mut a), train(4, &mut b));
+        assert_eq!(a.hidden_layers()[0].weights, b.hidden_layers()[0].weights);
+
+        // stopping, saving and resuming from the checkpoint continues bit-identically
+        let mut resumed = builder.build().unwrap();
+        train(2, &mut resumed);
+        let mut resumed = Network::from_bytes(&resumed.to_bytes().unwrap()).unwrap();
+        train(2, &mut resumed);
+        assert_eq!(a.output_layer().weights, resumed.output_layer().weights);
+        assert_eq!(a.hidden_layers()[0].weights, resumed.hidden_layers()[0].weights);
+
+        // the trainer seed overrides the rng state of the network
+        let mut c = NetworkBuilder::new(1).dense(1, Activation::Identity).build().unwrap();
+        let mut d = c.clone();
+        d.set_seed(9);
+        let config = TrainConfig {
+            epochs: 3,
+            batch_size: 4,
+            ..TrainConfig::default()
+        };
+        let history_c = Trainer::new(Sgd::new(0.1)).with_seed(1).fit(&mut c, &line(), config.clone()).unwrap();
+        let history_d = Trainer::new(Sgd::new(0.1)).with_seed(1).fit(&mut d, &line(), config).unwrap();
+        assert_eq!(history_c, history_d);
+    }
+
+    #[test]
+    fn regularizer_test() {
+        let config = TrainConfig {
+            epochs: 50,
+            batch_size: 5,
+            ..TrainConfig::default()
+        };
+        let norm = |net: &Network| net.hidden_layers()[0].weights.mapv(|w| w * w).sum().sqrt();
+        let build = |regularizer: Regularizer| {
+            NetworkBuilder::new(1)
+                .seed(5)
+                .regularizer(regularizer)
+                .dense(8, Activation::Tanh)
+                .dense(1, Activation::Identity)
+                .build()
+                .unwrap()
+        };
+
+        let mut plain = build(Regularizer::default());
+        Trainer::new(Adam::new(0.05)).fit(&mut plain, &line(), config.clone()).unwrap();
+
+        let mut decayed = build(Regularizer::default().weight_decay(0.5));
+        Trainer::new(Adam::new(0.05)).fit(&mut decayed, &line(), config.clone()).unwrap();
+        assert!(norm(&decayed) < norm(&plain));
+
+        let mut constrained = NetworkBuilder::new(1)
+            .regularizer(Regularizer::default().max_norm(0.5).non_negative(true))
+            .dense(8, Activation::Tanh)
+            .dense(1, Activation::Identity)
+            .build()
+            .unwrap();
+        Trainer::new(Adam::new(0.05)).fit(&mut constrained, &line(), config).unwrap();
+        let weights = &constrained.output_layer().weights;
+        assert!(weights.iter().all(|w| *w >= 0.0));
+        assert!(weights.mapv(|w| w * w).sum().sqrt() <= 0.5 + 1e-6);
+    }
+
+    #[test]
+    fn dropout_test() {
+        let mut network = NetworkBuilder::new(1)
+            .seed(6)
+            .dense(32, Activation::Tanh)
+            .dropout(0.2)
+            .dense(1, Activation::Identity)
+            .build()
+            .unwrap();
+        let before = evaluate(&mut network, &line()).unwrap();
+        // evaluation runs without dropout and keeps the mode
+        assert_eq!(before, evaluate(&mut network, &line()).unwrap());
+        assert!(network.is_training());
+
+        network.eval();
+        let config = TrainConfig {
+            epochs: 100,
+            batch_size: 4,
+            ..TrainConfig::default()
+        };
+        Trainer::new(Adam::new(0.01)).fit(&mut network, &line(), config).unwrap();
+        assert!(!network.is_training());
+        assert!(evaluate(&mut network, &line()).unwrap() < before / 10.0);
+
+        // also after a failed fit
+        let wide = Dataset::new(Array2::zeros((4, 2)), Array2::zeros((4, 1))).unwrap();
+        assert!(Trainer::new(Adam::new(0.01)).fit(&mut network, &wide, TrainConfig::default()).is_err());
+        assert!(!network.is_training());
+    }
+
+    #[test]
+    fn batch_norm_test() {
+        let mut builder = NetworkBuilder::new(1).seed(7).bias(false);
+        for _ in 0..4 {
+            builder = builder.dense(16, Activation::Identity).batch_norm().activation(Activation::Sigmoid);
+        }
+        let mut network = builder.bias(true).dense(1, Activation::Identity).build().unwrap();
+        let before = evaluate(&mut network, &line()).unwrap();
+
+        let config = TrainConfig {
+            epochs: 100,
+            batch_size: 5,
+            ..TrainConfig::default()
+        };
+        Trainer::new(Adam::new(0.01)).fit(&mut network, &line(), config).unwrap();
+        // inference uses the running statistics collected during training
+        let Block::BatchNorm(norm) = &network.blocks()[1] else { unreachable!() };
+        assert!(norm.running_mean.iter().any(|m| m.abs() > 1e-3));
+        assert!(evaluate(&mut network, &line()).unwrap() < before / 10.0);
+    }
+
+    #[test]
+    fn conv2d_test() {
+        // a vertical bar in every column and a horizontal bar in every row of 5x5 images
+        let features = Array2::from_shape_fn((10, 25), |(i, j)| match i < 5 {
+            true => (j % 5 == i) as u8 as f32,
+            false => (j / 5 == i - 5) as u8 as f32,
+        });
+        let targets = Array2::from_shape_fn((10, 2), |(i, j)| ((i < 5) == (j == 0)) as u8 as f32);
+        let bars = Dataset::new(features.clone(), targets.clone()).unwrap();
+
+        let mut network = NetworkBuilder::new(25)
+            .seed(11)
+            .reshape(1, 5, 5)
+            .conv2d(4, (3, 3), (1, 1), Padding::Same, Activation::Relu)
+            .max_pool2d((2, 2), (2, 2))
+            .flatten()
+            .dense(2, Activation::Softmax)
+            .build()
+            .unwrap();
+        let config = TrainConfig {
+            epochs: 100,
+            batch_size: 5,
+            ..TrainConfig::default()
+        };
+        Trainer::new(Adam::new(0.01)).fit(&mut network, &bars, config).unwrap();
+        let outputs = network.forward_batch(&features).unwrap();
+        for (output, target) in outputs.rows().into_iter().zip(targets.rows()) {
+            assert_eq!(target[0] == 1.0, output[0] > output[1], "{}", outputs);
+        }
+    }
+
+    #[test]
+    fn conv1d_test() {
+        // a rise and fall or a fall and rise anywhere in a sequence of 12
+        let features = Array2::from_shape_fn((22, 12), |(i, j)| match (i % 11, j) {
+            (at, j) if j == at => 1.0 - 2.0 * (i / 11) as f32,
+            (at, j) if j == at + 1 => -1.0 + 2.0 * (i / 11) as f32,
+            _ => 0.0,
+        });
+        let targets = Array2::from_shape_fn((22, 2), |(i, j)| ((i < 11) == (j == 0)) as u8 as f32);
+        let bumps = Dataset::new(features.clone(), targets.clone()).unwrap();
+
+        let mut network = NetworkBuilder::new(12)
+            .seed(12)
+            .reshape1d(1, 12)
+            .conv1d(4, 2, 1, 1, Padding::Causal, Activation::Relu)
+            .global_max_pool1d()
+            .dense(2, Activation::Softmax)
+            .build()
+            .unwrap();
+        let config = TrainConfig {
+            epochs: 100,
+            batch_size: 5,
+            ..TrainConfig::default()
+        };
+        Trainer::new(Adam::new(0.02)).fit(&mut network, &bumps, config).unwrap();
+        let outputs = network.forward_batch(&features).unwrap();
+        for (output, target) in outputs.rows().into_iter().zip(targets.rows()) {
+            assert_eq!(target[0] == 1.0, output[0] > output[1], "{}", outputs);
+        }
+    }
+}
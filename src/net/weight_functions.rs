@@ -1,6 +1,12 @@
+use std::f32::consts::PI;
+use std::fmt;
+
 use ndarray::prelude::*;
 use rand::{thread_rng, Rng};
 
+// draws from thread_rng, so nothing built with it is reproducible, and with the limit 1 / sqrt(n)
+// instead of the glorot one
+#[deprecated(note = "use Initializer, which draws from an explicit rng")]
 pub fn xavier_init(size: u32) -> Array1<f32> {
     let lower = -(1.0 / f32::sqrt(size as f32));
     let upper = -lower;
@@ -9,3 +15,145 @@ pub fn xavier_init(size: u32) -> Array1<f32> {
         .collect();
     Array::from_vec(data)
 }
+
+// how the (fan_in, fan_out) weight matrix of a layer is filled,
+// the uniform variants sample from [-limit, limit] and the normal ones from N(0, std^2)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Initializer {
+    // limit sqrt(6 / (fan_in + fan_out)), suits sigmoid, tanh and softmax
+    #[default]
+    GlorotUniform,
+    // std sqrt(2 / (fan_in + fan_out))
+    GlorotNormal,
+    // limit sqrt(6 / fan_in), suits relu and its variants
+    HeUniform,
+    // std sqrt(2 / fan_in)
+    HeNormal,
+    // limit sqrt(3 / fan_in), suits selu
+    LecunUniform,
+    // std sqrt(1 / fan_in)
+    LecunNormal,
+    // a random matrix with orthonormal rows or columns, scaled by gain
+    Orthogonal(f32),
+    Constant(f32),
+    Zeros,
+}
+
+impl Initializer {
+    pub fn init<R: Rng + ?Sized>(&self, fan_in: usize, fan_out: usize, rng: &mut R) -> Array2<f32> {
+        let (fan_in_f, fan_sum) = (fan_in as f32, (fan_in + fan_out) as f32);
+        let shape = (fan_in, fan_out);
+        match *self {
+            Initializer::GlorotUniform => uniform(shape, (6.0 / fan_sum).sqrt(), rng),
+            Initializer::GlorotNormal => normal(shape, (2.0 / fan_sum).sqrt(), rng),
+            Initializer::HeUniform => uniform(shape, (6.0 / fan_in_f).sqrt(), rng),
+            Initializer::HeNormal => normal(shape, (2.0 / fan_in_f).sqrt(), rng),
+            Initializer::LecunUniform => uniform(shape, (3.0 / fan_in_f).sqrt(), rng),
+            Initializer::LecunNormal => normal(shape, (1.0 / fan_in_f).sqrt(), rng),
+            Initializer::Orthogonal(gain) => orthogonal(shape, rng) * gain,
+            Initializer::Constant(value) => Array2::from_elem(shape, value),
+            Initializer::Zeros => Array2::zeros(shape),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Initializer::GlorotUniform => "glorot_uniform",
+            Initializer::GlorotNormal => "glorot_normal",
+            Initializer::HeUniform => "he_uniform",
+            Initializer::HeNormal => "he_normal",
+            Initializer::LecunUniform => "lecun_uniform",
+            Initializer::LecunNormal => "lecun_normal",
+            Initializer::Orthogonal(_) => "orthogonal",
+            Initializer::Constant(_) => "constant",
+            Initializer::Zeros => "zeros",
+        }
+    }
+}
+
+impl fmt::Display for Initializer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Initializer::Orthogonal(value) | Initializer::Constant(value) => write!(f, "{}({})", self.name(), value),
+            _ => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+fn uniform<R: Rng + ?Sized>(shape: (usize, usize), limit: f32, rng: &mut R) -> Array2<f32> {
+    Array2::from_shape_simple_fn(shape, || rng.gen_range(-limit..=limit))
+}
+
+// box-muller, rand itself has no normal distribution
+fn standard_normal<R: Rng + ?Sized>(rng: &mut R) -> f32 {
+    let u1: f32 = 1.0 - rng.gen::<f32>();
+    let u2: f32 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+fn normal<R: Rng + ?Sized>(shape: (usize, usize), std: f32, rng: &mut R) -> Array2<f32> {
+    Array2::from_shape_simple_fn(shape, || std * standard_normal(rng))
+}
+
+// gram-schmidt on the columns of a gaussian matrix, the wider side gets transposed
+// so that the result has orthonormal columns when rows >= cols and orthonormal rows otherwise
+fn orthogonal<R: Rng + ?Sized>((rows, cols): (usize, usize), rng: &mut R) -> Array2<f32> {
+    if rows < cols {
+        return orthogonal((cols, rows), rng).reversed_axes();
+    }
+    let mut q = normal((rows, cols), 1.0, rng);
+    for j in 0..cols {
+        for k in 0..j {
+            let projection = q.column(j).dot(&q.column(k));
+            let basis = q.column(k).to_owned();
+            q.column_mut(j).scaled_add(-projection, &basis);
+        }
+        let norm = q.column(j).dot(&q.column(j)).sqrt();
+        q.column_mut(j).mapv_inplace(|v| v / norm);
+    }
+    q
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn initializer_test() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let weights = Initializer::GlorotUniform.init(100, 200, &mut rng);
+        assert_eq!((100, 200), weights.dim());
+        let limit = (6.0f32 / 300.0).sqrt();
+        assert!(weights.iter().all(|w| w.abs() <= limit));
+
+        // the sample standard deviation is close to sqrt(2 / fan_in)
+        let weights = Initializer::HeNormal.init(50, 400, &mut rng);
+        let std = (weights.mapv(|w| w * w).mean().unwrap()).sqrt();
+        assert!((std - 0.2).abs() < 0.01, "{}", std);
+
+        assert_eq!(Array2::from_elem((2, 3), 0.5), Initializer::Constant(0.5).init(2, 3, &mut rng));
+        assert_eq!(Array2::<f32>::zeros((3, 2)), Initializer::Zeros.init(3, 2, &mut rng));
+
+        // the same seed gives the same weights
+        let a = Initializer::LecunNormal.init(4, 4, &mut StdRng::seed_from_u64(7));
+        let b = Initializer::LecunNormal.init(4, 4, &mut StdRng::seed_from_u64(7));
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn orthogonal_test() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for (rows, cols) in [(6, 3), (3, 6), (4, 4)] {
+            let q = Initializer::Orthogonal(1.0).init(rows, cols, &mut rng);
+            let gram = match rows >= cols {
+                true => q.t().dot(&q),
+                false => q.dot(&q.t()),
+            };
+            let identity = Array2::<f32>::eye(rows.min(cols));
+            assert!((gram - identity).iter().all(|d| d.abs() < 1e-5));
+        }
+    }
+}